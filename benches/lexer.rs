@@ -0,0 +1,120 @@
+//! Time of each compiler stage on generated programs of doubling size, up to about 1 MB.
+//! Run with `cargo bench`.
+//!
+//! Covers preprocessing (comments, `#define`, `#ifdef` and macro expansion), lexing,
+//! parsing and code generation of a single unit, each on the output of the stage before.
+//! Parsing includes the lexing it starts with. `#include`, assembling, linking and the
+//! driver's file handling aren't covered.
+//!
+//! Every stage should be linear. Times are in ns per source byte for each size, and as
+//! the slope of a least squares fit over all sizes, which is the cost of each extra byte
+//! without the fixed costs that dominate small programs. Per byte times can step up once
+//! the program and its tokens no longer fit in cache, like for any code allocating that
+//! much. Superlinear work shows as per byte times that keep growing with every doubling.
+
+use cc::lexer::{Lexer, Token, TokenType};
+use cc::parser::Parser;
+use cc::preprocess::Preprocessor;
+use cc::Session;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Functions using most token kinds, with comments, literals and macros.
+/// Every tenth function adds a definition and a conditional block.
+fn program(nfunctions: usize) -> String {
+    let mut prog: String = String::new();
+    for i in 0..nfunctions {
+        if i % 10 == 0 {
+            prog.push_str(&format!("#define LIMIT{i} {i}\n#ifdef LIMIT{i}\n#define SCALE 0x2f\n#endif\n"));
+        }
+        let limit: usize = i - i % 10;
+        prog.push_str(&format!(
+            "/* function {i} */\nint f{i}(int a, int b) {{\n\tint x = a + b * SCALE - 'c';\n\tif (x <= LIMIT{limit} && a != b) {{\n\t\tx = x / 2; // halve\n\t}}\n\treturn x;\n}}\n\n",
+        ));
+    }
+    prog
+}
+
+/// Fastest of a few runs
+fn time<T>(mut f: impl FnMut() -> T) -> Duration {
+    (0..5)
+        .map(|_| {
+            let start: Instant = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+/// Slope in ns per byte of the least squares line through (bytes, time)
+fn slope(samples: &[(usize, Duration)]) -> f64 {
+    let n: f64 = samples.len() as f64;
+    let mean_x: f64 = samples.iter().map(|x| x.0 as f64).sum::<f64>() / n;
+    let mean_y: f64 = samples.iter().map(|x| x.1.as_nanos() as f64).sum::<f64>() / n;
+    let (mut cov, mut var): (f64, f64) = (0.0, 0.0);
+    for (bytes, d) in samples {
+        let dx: f64 = *bytes as f64 - mean_x;
+        cov += dx * (d.as_nanos() as f64 - mean_y);
+        var += dx * dx;
+    }
+    cov / var
+}
+
+fn main() {
+    const STAGES: [&str; 4] = ["preprocess", "lex", "parse", "generate"];
+    let session: Session = Session::new(Default::default());
+    // Reused by every lexing run, so it doesn't reallocate as it grows
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut samples: Vec<[(usize, Duration); 4]> = Vec::new();
+
+    println!("{:>10} {:>10} {:>12} {:>12} {:>12} {:>12}", "bytes", "tokens", STAGES[0], STAGES[1], STAGES[2], STAGES[3]);
+    for nfunctions in [250, 500, 1000, 2000, 4000, 8000] {
+        let prog: String = program(nfunctions);
+        let bytes: usize = prog.len();
+
+        let preprocess: Duration = time(|| {
+            let mut preprocessor: Preprocessor = Preprocessor::new(&prog, "bench.c");
+            preprocessor.preprocess().unwrap();
+            preprocessor
+        });
+        let processed: String = session.preprocess(&prog, "bench.c").unwrap();
+
+        let lex: Duration = time(|| {
+            tokens.clear();
+            let mut lexer: Lexer = Lexer::new(&processed);
+            loop {
+                let tok: Token = lexer.next().unwrap();
+                let eof: bool = tok.ttype == TokenType::Eof;
+                tokens.push(tok);
+                if eof {
+                    break;
+                }
+            }
+        });
+
+        let parse: Duration = time(|| Parser::new(&processed).unwrap().parse().unwrap());
+        let root = session.parse(&processed).unwrap();
+        let generate: Duration = time(|| session.generate(&root).unwrap());
+
+        let row: [(usize, Duration); 4] = [preprocess, lex, parse, generate].map(|x| (bytes, x));
+        let per_byte = |d: Duration| d.as_nanos() as f64 / bytes as f64;
+        println!(
+            "{:>10} {:>10} {:>12.2} {:>12.2} {:>12.2} {:>12.2}",
+            bytes,
+            tokens.len(),
+            per_byte(preprocess),
+            per_byte(lex),
+            per_byte(parse),
+            per_byte(generate)
+        );
+        samples.push(row);
+    }
+
+    print!("{:>21}", "fitted ns/byte");
+    for (i, _) in STAGES.iter().enumerate() {
+        let stage: Vec<(usize, Duration)> = samples.iter().map(|x| x[i]).collect();
+        print!(" {:>12.2}", slope(&stage));
+    }
+    println!();
+}
@@ -0,0 +1,74 @@
+// Exits with the number of failed checks
+
+// The last two don't fit in rdi to r9 and go on the stack
+long eight(long a, int b, char c, long d, int e, long f, char g, int h)
+{
+    return a + b * 10 + c * 100 + d * 1000 + e * 10000 + f * 100000 + g * 1000000 + h * 10000000;
+}
+
+// Nine doubles, xmm0 to xmm7 and one on the stack
+double nine(double a, double b, double c, double d, double e, double f, double g, double h, double i)
+{
+    return a + b * 2.0 + c * 3.0 + d * 4.0 + e * 5.0 + f * 6.0 + g * 7.0 + h * 8.0 + i * 9.0;
+}
+
+// Integer and float params take registers from their own class
+double mixed(int a, float b, long c, double d, char e)
+{
+    return a * 1.0 + b * 10.0 + c * 100.0 + d * 1000.0 + e * 10000.0;
+}
+
+int sub(int a, int b)
+{
+    return a - b;
+}
+
+int deref(int *p, int n)
+{
+    return *p + n;
+}
+
+int main()
+{
+    int fails = 0;
+
+    if (eight(1, 2, 3, 4, 5, 6, 7, 8) != 87654321)
+        fails = fails + 1;
+    if (nine(1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0) != 45.0)
+        fails = fails + 1;
+    if (nine(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 2.5) != 22.5)
+        fails = fails + 1;
+    if (mixed(1, 2.0f, 3, 4.0, 5) != 54321.0)
+        fails = fails + 1;
+
+    // Args are converted to the param types
+    if (mixed(1.9, 2, 3, 4, 5) != 54321.0)
+        fails = fails + 1;
+
+    // Args name caller variables, even when they match param names
+    int a = 10;
+    int b = 3;
+    if (sub(b, a) != -7)
+        fails = fails + 1;
+    if (sub(a, b) != 7)
+        fails = fails + 1;
+
+    // Calls in args don't clobber the registers of earlier args
+    if (sub(sub(10, 1), sub(5, 3)) != 7)
+        fails = fails + 1;
+    if (eight(1, 2, 3, 4, 5, 6, 7, sub(9, 1)) != 87654321)
+        fails = fails + 1;
+
+    // Stack args stay together when evaluating them grows the stack
+    int k = 3;
+    if (eight(1, 2, 3, 4, 5, 6, k * 2 + 1, k * 3 - 1 + 0) != 87654321)
+        fails = fails + 1;
+    if (nine(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, k * 1.5 + 1.0) != 13.5 * 3.0 + 9.0)
+        fails = fails + 1;
+
+    int x = 40;
+    if (deref(&x, 2) != 42)
+        fails = fails + 1;
+
+    return fails;
+}
@@ -0,0 +1,77 @@
+// Exits with the number of failed checks
+int main()
+{
+    int fails = 0;
+
+    int a = 0 - 7;
+    int b = 2;
+    if (a / b != 0 - 3)
+        fails = fails + 1;
+    if (a % b != 0 - 1)
+        fails = fails + 1;
+    if (7 / (0 - 2) != 0 - 3)
+        fails = fails + 1;
+    if (7 % (0 - 2) != 1)
+        fails = fails + 1;
+    if (a / (0 - 2) != 3)
+        fails = fails + 1;
+    if (a % (0 - 2) != 0 - 1)
+        fails = fails + 1;
+    if (a * b != 0 - 14)
+        fails = fails + 1;
+    if (a * (0 - 3) != 21)
+        fails = fails + 1;
+    if (a / b * b + a % b != a)
+        fails = fails + 1;
+
+    long la = 0 - 9000000000;
+    long lb = 0 - 4;
+    if (la / lb != 2250000000)
+        fails = fails + 1;
+    if (la % 7 != 0 - 1)
+        fails = fails + 1;
+    if (la * lb != 36000000000)
+        fails = fails + 1;
+
+    // Unsigned operands divide without sign extension
+    unsigned int ua = 4294967289;
+    unsigned int ub = 2;
+    if (ua / ub != 2147483644)
+        fails = fails + 1;
+    if (ua % ub != 1)
+        fails = fails + 1;
+    if (ua * ub != 4294967282)
+        fails = fails + 1;
+
+    // Chars promote to int
+    char c = 0 - 9;
+    if (c / 4 != 0 - 2)
+        fails = fails + 1;
+    if (c % 4 != 0 - 1)
+        fails = fails + 1;
+
+    // unsigned char promotes without sign extension, and wraps when stored
+    unsigned char uc = 250;
+    if (uc / 5 != 50)
+        fails = fails + 1;
+    if (uc + 10 != 260)
+        fails = fails + 1;
+    uc = uc + 10;
+    if (uc != 4)
+        fails = fails + 1;
+    uc = 255;
+    double d = uc;
+    if (d != 255.0)
+        fails = fails + 1;
+
+    int r = 100;
+    r %= 7;
+    if (r != 2)
+        fails = fails + 1;
+    r = 0 - 100;
+    r /= 7;
+    if (r != 0 - 14)
+        fails = fails + 1;
+
+    return fails;
+}
@@ -0,0 +1,148 @@
+// Exits with the number of failed checks
+struct counter {
+    int hits;
+    int total;
+};
+
+// Counts how often the lvalue it returns is evaluated
+int *track(int *p, int *calls)
+{
+    *calls = *calls + 1;
+    return p;
+}
+
+int main()
+{
+    int fails = 0;
+
+    // Every compound operator on a variable
+    int x = 6;
+    x += 4;
+    if (x != 10)
+        fails = fails + 1;
+    x -= 3;
+    if (x != 7)
+        fails = fails + 1;
+    x *= 6;
+    if (x != 42)
+        fails = fails + 1;
+    x /= 5;
+    if (x != 8)
+        fails = fails + 1;
+    x %= 5;
+    if (x != 3)
+        fails = fails + 1;
+    x <<= 4;
+    if (x != 48)
+        fails = fails + 1;
+    x >>= 2;
+    if (x != 12)
+        fails = fails + 1;
+    x &= 10;
+    if (x != 8)
+        fails = fails + 1;
+    x |= 3;
+    if (x != 11)
+        fails = fails + 1;
+    x ^= 6;
+    if (x != 13)
+        fails = fails + 1;
+
+    // The result is the assigned value
+    int y = (x += 2) * 2;
+    if (y != 30)
+        fails = fails + 1;
+    if (x != 15)
+        fails = fails + 1;
+
+    // Pre gives the new value, post the old one
+    int i = 5;
+    if (++i != 6)
+        fails = fails + 1;
+    if (i != 6)
+        fails = fails + 1;
+    if (i++ != 6)
+        fails = fails + 1;
+    if (i != 7)
+        fails = fails + 1;
+    if (--i != 6)
+        fails = fails + 1;
+    if (i != 6)
+        fails = fails + 1;
+    if (i-- != 6)
+        fails = fails + 1;
+    if (i != 5)
+        fails = fails + 1;
+
+    // Struct members
+    struct counter c = (struct counter){ .hits = 0, .total = 100 };
+    c.hits++;
+    ++c.hits;
+    c.total -= 40;
+    c.total *= 2;
+    if (c.hits != 2)
+        fails = fails + 1;
+    if (c.total != 120)
+        fails = fails + 1;
+    if (c.hits-- != 2)
+        fails = fails + 1;
+    if (c.hits != 1)
+        fails = fails + 1;
+
+    // Dereferenced pointers
+    int v = 1;
+    int *p = &v;
+    *p += 9;
+    (*p)++;
+    if (v != 11)
+        fails = fails + 1;
+    if (--*p != 10)
+        fails = fails + 1;
+    if (v != 10)
+        fails = fails + 1;
+    if ((*p)-- != 10)
+        fails = fails + 1;
+    if (v != 9)
+        fails = fails + 1;
+
+    // The lvalue is evaluated once
+    int calls = 0;
+    *track(&v, &calls) += 5;
+    if (v != 14)
+        fails = fails + 1;
+    if (calls != 1)
+        fails = fails + 1;
+    calls = 0;
+    (*track(&v, &calls))++;
+    if (v != 15)
+        fails = fails + 1;
+    if (calls != 1)
+        fails = fails + 1;
+    calls = 0;
+    if (--*track(&v, &calls) != 14)
+        fails = fails + 1;
+    if (calls != 1)
+        fails = fails + 1;
+
+    // Other types
+    char ch = 'a';
+    ch += 2;
+    ch++;
+    if (ch != 'd')
+        fails = fails + 1;
+    unsigned int u = 0;
+    u--;
+    if (u != 4294967295)
+        fails = fails + 1;
+    double d = 1.5;
+    d *= 3.0;
+    d++;
+    if (d != 5.5)
+        fails = fails + 1;
+    long l = 9000000000;
+    l += l;
+    if (l != 18000000000)
+        fails = fails + 1;
+
+    return fails;
+}
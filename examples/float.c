@@ -0,0 +1,155 @@
+// Exits with the number of failed checks
+double half(double x)
+{
+    return x / 2;
+}
+
+float scale(float x, int n)
+{
+    return x * n;
+}
+
+int truncate(double x)
+{
+    return x;
+}
+
+int main()
+{
+    int fails = 0;
+
+    double d = 1.5;
+    float f = 2.25f;
+    if (d + f != 3.75)
+        fails = fails + 1;
+    if (d * 4 != 6.0)
+        fails = fails + 1;
+    if (f - 0.25f != 2)
+        fails = fails + 1;
+    if (7.0 / 2 != 3.5)
+        fails = fails + 1;
+
+    // Literals
+    double e = 1.25e2;
+    if (e != 125)
+        fails = fails + 1;
+    if (2.5e-1 != .25)
+        fails = fails + 1;
+    if (1.f != 1)
+        fails = fails + 1;
+    long double ld = 3.;
+    if (ld != 3.0L)
+        fails = fails + 1;
+
+    // Conversions truncate toward zero
+    int i = d * 3;
+    if (i != 4)
+        fails = fails + 1;
+    double neg = 0 - 2.75;
+    int ni = neg;
+    if (ni != 0 - 2)
+        fails = fails + 1;
+    long big = 3000000000.0;
+    if (big != 3000000000)
+        fails = fails + 1;
+    unsigned int u = 4000000000;
+    double ud = u;
+    if (ud != 4000000000.0)
+        fails = fails + 1;
+    char c = 0 - 3;
+    float cf = c;
+    if (cf != 0 - 3.0f)
+        fails = fails + 1;
+    float narrow = 0.1;
+    double wide = narrow;
+    if (wide == 0.1)
+        fails = fails + 1;
+    if (wide != 0.1f)
+        fails = fails + 1;
+
+    // Comparisons
+    if (!(d < f))
+        fails = fails + 1;
+    if (d > f)
+        fails = fails + 1;
+    if (!(d >= 1.5))
+        fails = fails + 1;
+    if (!d)
+        fails = fails + 1;
+    double zero = 0.0;
+    if (zero)
+        fails = fails + 1;
+
+    // Calls pass and return floats
+    if (half(5) != 2.5)
+        fails = fails + 1;
+    if (scale(1.5f, 3) != 4.5)
+        fails = fails + 1;
+    if (truncate(9.99) != 9)
+        fails = fails + 1;
+
+    // Assignment forms
+    double acc = 1;
+    acc += 0.5;
+    acc *= 4;
+    acc -= 1;
+    acc /= 2;
+    if (acc != 2.5)
+        fails = fails + 1;
+    double old = acc++;
+    if (old != 2.5)
+        fails = fails + 1;
+    if (acc != 3.5)
+        fails = fails + 1;
+    double *p = &acc;
+    *p = 0.5;
+    if (acc != 0.5)
+        fails = fails + 1;
+    double sel = fails ? 1.0 : 2;
+    if (sel != 2.0)
+        fails = fails + 1;
+
+    // NaN is unordered, every comparison but != is false, and it's true as a condition
+    double zero = 0.0;
+    double n = zero / zero;
+    double one = 1.0;
+    if (n == n)
+        fails = fails + 1;
+    if (!(n != n))
+        fails = fails + 1;
+    if (n < one)
+        fails = fails + 1;
+    if (n <= one)
+        fails = fails + 1;
+    if (n > one)
+        fails = fails + 1;
+    if (n >= one)
+        fails = fails + 1;
+    if (one < n)
+        fails = fails + 1;
+    if (one >= n)
+        fails = fails + 1;
+    if (n == one)
+        fails = fails + 1;
+    int taken = 0;
+    if (n)
+        taken = 1;
+    if (!taken)
+        fails = fails + 1;
+    if (!n)
+        fails = fails + 1;
+    if ((n ? 1 : 0) != 1)
+        fails = fails + 1;
+    if (!(n && one))
+        fails = fails + 1;
+    float fzero = 0.0f;
+    float fn = fzero / fzero;
+    if (fn == fn)
+        fails = fails + 1;
+    if (fn < 1.0f)
+        fails = fails + 1;
+    if (fn >= 1.0f)
+        fails = fails + 1;
+
+    return fails;
+}
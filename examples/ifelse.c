@@ -0,0 +1,98 @@
+// Exits with the number of failed checks
+int classify(int n)
+{
+    if (n < 0)
+        return 0 - 1;
+    else if (n == 0)
+        return 0;
+    else if (n < 10)
+        return 1;
+    else
+        return 2;
+}
+
+int sign_of_product(int a, int b)
+{
+    if (a < 0) {
+        if (b < 0)
+            return 1;
+        else
+            return 0 - 1;
+    } else {
+        if (b < 0) {
+            return 0 - 1;
+        }
+        return 1;
+    }
+}
+
+int main()
+{
+    int fails = 0;
+
+    if (classify(0 - 5) != 0 - 1)
+        fails = fails + 1;
+    if (classify(0) != 0)
+        fails = fails + 1;
+    if (classify(7) != 1)
+        fails = fails + 1;
+    if (classify(12) != 2)
+        fails = fails + 1;
+
+    // Arguments are variables, temporaries in arguments aren't supported yet
+    int m2 = 0 - 2;
+    int m3 = 0 - 3;
+    if (sign_of_product(m2, m3) != 1)
+        fails = fails + 1;
+    if (sign_of_product(m2, 3) != 0 - 1)
+        fails = fails + 1;
+    if (sign_of_product(2, m3) != 0 - 1)
+        fails = fails + 1;
+    if (sign_of_product(2, 3) != 1)
+        fails = fails + 1;
+
+    // Dangling else goes with the inner if
+    int x = 0;
+    if (1)
+        if (0)
+            x = 1;
+        else
+            x = 2;
+    if (x != 2)
+        fails = fails + 1;
+
+    x = 0;
+    if (0)
+        if (1)
+            x = 1;
+        else
+            x = 2;
+    if (x != 0)
+        fails = fails + 1;
+
+    // Only one branch runs
+    int taken = 0;
+    int i = 0;
+    for (i = 0; i < 4; i++) {
+        if (i == 0) {
+            taken += 1;
+        } else if (i == 1) {
+            taken += 10;
+        } else {
+            taken += 100;
+        }
+    }
+    if (taken != 211)
+        fails = fails + 1;
+
+    // Branches that push temporaries leave the stack usable for calls
+    int y = 3;
+    if (y > 5)
+        y = y * 2 + 1;
+    else
+        y = y * 3 + 1;
+    if (classify(y) != 2)
+        fails = fails + 1;
+
+    return fails;
+}
@@ -0,0 +1,236 @@
+// Exits with the number of failed checks
+
+// First i with i * i > limit
+int first_square_above(int limit)
+{
+    int i = 0;
+    for (i = 0; i < 100; i++) {
+        if (i * i > limit)
+            break;
+    }
+    return i;
+}
+
+// Sum of the odd numbers below n
+int odd_sum(int n)
+{
+    int sum = 0;
+    int i = 0;
+    while (i < n) {
+        i++;
+        if (i % 2 == 0)
+            continue;
+        if (i >= n)
+            break;
+        sum += i;
+    }
+    return sum;
+}
+
+// Number of digits, a do while runs its body before the check
+int digits(int x)
+{
+    int n = 0;
+    do {
+        n++;
+        x = x / 10;
+    } while (x != 0);
+    return n;
+}
+
+// continue in a do while goes to the check
+int do_continue(int n)
+{
+    int count = 0;
+    int i = 0;
+    do {
+        i++;
+        if (i == 2)
+            continue;
+        count++;
+    } while (i < n);
+    return count;
+}
+
+// break leaves the inner loop, continue goes to the next iteration of its own loop
+int pairs(int n)
+{
+    int count = 0;
+    for (int i = 0; i < n; i++) {
+        for (int j = 0; j < n; j++) {
+            if (j == i)
+                continue;
+            if (j > i)
+                break;
+            count++;
+        }
+    }
+    return count;
+}
+
+// In a switch, break leaves the switch and continue goes to the loop around it
+int tokens(int n)
+{
+    int score = 0;
+    for (int i = 0; i < n; i++) {
+        switch (i % 4) {
+        case 0:
+            score += 1;
+            break;
+        case 1:
+            continue;
+        case 2:
+            score += 100;
+        default:
+            score += 10;
+            break;
+        }
+        score += 1000;
+    }
+    return score;
+}
+
+// Gotos jump forward and backward, and out of nested loops
+int find(int target)
+{
+    int found = -1;
+    for (int i = 0; i < 10; i++) {
+        for (int j = 0; j < 10; j++) {
+            if (i * 10 + j == target) {
+                found = i * 10 + j;
+                goto done;
+            }
+        }
+    }
+done:
+    return found;
+}
+
+int countdown(int n)
+{
+    int steps = 0;
+again:
+    if (n > 0) {
+        n--;
+        steps++;
+        goto again;
+    }
+    return steps;
+}
+
+// Labels are scoped to their function
+int same_label(int x)
+{
+    goto done;
+    x = 0;
+done:
+    return x;
+}
+
+// Labels can follow empty statements and be the body of if and loops
+int labels(int n)
+{
+    int i = 0;
+    ; start: i++;
+    if (i < n)
+        goto start;
+    if (n == -1)
+        goto skip;
+    if (n == -2)
+        goto back;
+    return i;
+    if (n < 0)
+        skip: i = 100;
+    while (i > 98)
+        back: i--;
+    return i;
+empty:
+}
+
+int main()
+{
+    int fails = 0;
+
+    if (first_square_above(50) != 8)
+        fails = fails + 1;
+    if (odd_sum(10) != 25)
+        fails = fails + 1;
+    if (odd_sum(9) != 16)
+        fails = fails + 1;
+    if (digits(0) != 1)
+        fails = fails + 1;
+    if (digits(12345) != 5)
+        fails = fails + 1;
+    if (do_continue(5) != 4)
+        fails = fails + 1;
+    if (pairs(4) != 6)
+        fails = fails + 1;
+    if (tokens(8) != 6242)
+        fails = fails + 1;
+    if (find(37) != 37)
+        fails = fails + 1;
+    if (find(200) != -1)
+        fails = fails + 1;
+    if (countdown(6) != 6)
+        fails = fails + 1;
+    if (same_label(3) != 3)
+        fails = fails + 1;
+    if (labels(4) != 4)
+        fails = fails + 1;
+    if (labels(-1) != 98)
+        fails = fails + 1;
+    if (labels(-2) != 0)
+        fails = fails + 1;
+
+    // A while checks before its first iteration
+    int runs = 0;
+    while (runs > 0)
+        runs = runs + 10;
+    if (runs != 0)
+        fails = fails + 1;
+
+    // An empty body is its own statement and doesn't take the next one
+    int m = 5;
+    while (m-- > 0)
+        ;
+    runs = 0;
+    if (m > 0);
+    else
+        runs = 1;
+    if (m != -1)
+        fails = fails + 1;
+    if (runs != 1)
+        fails = fails + 1;
+
+    // A for without a condition ends with break
+    int n = 0;
+    for (;;) {
+        n++;
+        if (n == 7)
+            break;
+    }
+    if (n != 7)
+        fails = fails + 1;
+
+    // break in a switch in a while
+    int state = 0;
+    int steps = 0;
+    while (state != 3) {
+        switch (state) {
+        case 0:
+            state = 2;
+            break;
+        case 2:
+            state = 1;
+            break;
+        case 1:
+            state = 3;
+            break;
+        }
+        steps++;
+    }
+    if (steps != 3)
+        fails = fails + 1;
+
+    return fails;
+}
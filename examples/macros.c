@@ -0,0 +1,63 @@
+// Exits with the number of failed checks
+#define SIZE 3
+#define TWICE SIZE * 2
+
+// A redefinition replaces the earlier one everywhere, including in other macros
+#define SIZE 5
+
+#ifndef SIZE
+#define SIZE 100
+#define REMOVED 1
+#endif
+
+#ifdef SIZE
+#define KEPT 7
+#define TWICE 12
+#endif
+
+#ifndef REMOVED
+#define REMOVED 0
+#endif
+
+// Directives in comments are ignored
+// #include "missing.h"
+/* #include "missing.h"
+#define SIZE 1 */
+/*
+#include "missing.h"
+*/
+
+// Comments in a definition aren't part of it, even when they span lines
+#define TWO 2 // two
+#define THREE 1 /* one
+   plus */ + 2
+#define FOUR /**/ 4
+
+int main()
+{
+    int fails = 0;
+
+    if (SIZE != 5)
+        fails = fails + 1;
+    if (TWICE != 12)
+        fails = fails + 1;
+    if (KEPT != 7)
+        fails = fails + 1;
+
+    // Definitions in removed contents don't exist
+    if (REMOVED != 0)
+        fails = fails + 1;
+
+    if (TWO + THREE != 5)
+        fails = fails + 1;
+    if (FOUR * 2 != 8)
+        fails = fails + 1;
+
+    // Comment markers in literals aren't comments
+    if ('/' != 47)
+        fails = fails + 1;
+    if ('/' + '*' != 89)
+        fails = fails + 1;
+
+    return fails;
+}
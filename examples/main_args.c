@@ -0,0 +1,36 @@
+// Exits with the number of failed checks.
+// Works with any arguments, e.g. cc run examples/main_args.c -- 1 2 3
+
+// Length of a null terminated string
+int length(char *s)
+{
+    int n = 0;
+    while (*s != 0) {
+        n++;
+        s++;
+    }
+    return n;
+}
+
+int main(int argc, char **argv)
+{
+    int fails = 0;
+
+    // The program name comes first
+    if (argc < 1)
+        fails = fails + 1;
+    if (length(*argv) == 0)
+        fails = fails + 1;
+
+    // argv ends with a null pointer after argc arguments
+    int n = 0;
+    char **p = argv;
+    while (*p != 0) {
+        n++;
+        p++;
+    }
+    if (n != argc)
+        fails = fails + 1;
+
+    return fails;
+}
@@ -0,0 +1,48 @@
+// Exits with the number of failed checks
+void set(int *x, int value)
+{
+    *x = value;
+}
+
+int get(int *x)
+{
+    return *x;
+}
+
+void swap(long *a, long *b)
+{
+    long t = *a;
+    *a = *b;
+    *b = t;
+}
+
+int main()
+{
+    int fails = 0;
+
+    // Pointer parameters write through to the caller's variables
+    int x = 1;
+    set(&x, 5);
+    if (x != 5)
+        fails = fails + 1;
+    if (get(&x) != 5)
+        fails = fails + 1;
+
+    long a = 3;
+    long b = 9000000000;
+    swap(&a, &b);
+    if (a != 9000000000)
+        fails = fails + 1;
+    if (b != 3)
+        fails = fails + 1;
+
+    // Pointers without an initializer are null
+    int *p;
+    if (p != 0)
+        fails = fails + 1;
+    p = &x;
+    if (*p != 5)
+        fails = fails + 1;
+
+    return fails;
+} // Ends without a newline
\ No newline at end of file
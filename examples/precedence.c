@@ -0,0 +1,117 @@
+// Exits with the number of failed checks
+// Each expression is compared with its parenthesized form, the operands are picked so
+// that grouping it any other way gives a different value.
+int main()
+{
+    int fails = 0;
+    int a = 7;
+    int b = 3;
+    int c = 2;
+    int d = 5;
+
+    // Left associative operators
+    if ((a - b - c) != ((a - b) - c))
+        fails = fails + 1;
+    if ((a / b / c) != ((a / b) / c))
+        fails = fails + 1;
+    if ((a - b + c) != ((a - b) + c))
+        fails = fails + 1;
+    if ((64 >> c >> 1) != ((64 >> c) >> 1))
+        fails = fails + 1;
+    if ((a < b < c) != ((a < b) < c))
+        fails = fails + 1;
+
+    // Multiplicative over additive over shift
+    if ((a + b * c) != (a + (b * c)))
+        fails = fails + 1;
+    if ((a * b + c * d) != ((a * b) + (c * d)))
+        fails = fails + 1;
+    if ((a - b * c - d) != ((a - (b * c)) - d))
+        fails = fails + 1;
+    if ((a % b * c) != ((a % b) * c))
+        fails = fails + 1;
+    if ((1 << c + 1) != (1 << (c + 1)))
+        fails = fails + 1;
+    if ((a + b << c) != ((a + b) << c))
+        fails = fails + 1;
+
+    // A lower operator after a higher one isn't taken into its right operand
+    if ((a / b * b + a % b != a) != ((((a / b) * b) + (a % b)) != a))
+        fails = fails + 1;
+    if ((a * b + c < d) != (((a * b) + c) < d))
+        fails = fails + 1;
+
+    // Relational over equality over bitwise
+    if ((a < b == c < d) != ((a < b) == (c < d)))
+        fails = fails + 1;
+    if ((b & d == 1) != (b & (d == 1)))
+        fails = fails + 1;
+    if ((a ^ b & c) != (a ^ (b & c)))
+        fails = fails + 1;
+    if ((a | b ^ d) != (a | (b ^ d)))
+        fails = fails + 1;
+    if ((c | b & d ^ a) != (c | ((b & d) ^ a)))
+        fails = fails + 1;
+
+    // && over ||
+    if ((a || b && 0) != (a || (b && 0)))
+        fails = fails + 1;
+    if ((0 && a || b) != ((0 && a) || b))
+        fails = fails + 1;
+    if ((a == b || c & 1 || d) != (((a == b) || (c & 1)) || d))
+        fails = fails + 1;
+
+    // Unary and postfix operators bind tighter than any binop
+    if ((!a + 1) != ((!a) + 1))
+        fails = fails + 1;
+    if ((~c & a) != ((~c) & a))
+        fails = fails + 1;
+    int x = 0;
+    if ((!x++) != 1)
+        fails = fails + 1;
+    if (x != 1)
+        fails = fails + 1;
+
+    // ?: is below || and groups to the right
+    if ((a > b ? c : d + 1) != ((a > b) ? c : (d + 1)))
+        fails = fails + 1;
+    if ((1 ? 2 : 0 ? 3 : 4) != (1 ? 2 : (0 ? 3 : 4)))
+        fails = fails + 1;
+    if ((0 || a ? b : c) != ((0 || a) ? b : c))
+        fails = fails + 1;
+    if ((a ? b : c, d) != 5)
+        fails = fails + 1;
+
+    // Assignments are below ?: and group to the right
+    int y = 0;
+    x = y = a;
+    if (x != 7)
+        fails = fails + 1;
+    if (y != 7)
+        fails = fails + 1;
+    x = 1;
+    y = 2;
+    x += y *= 3;
+    if (x != 7)
+        fails = fails + 1;
+    if (y != 6)
+        fails = fails + 1;
+    x = a > b ? c : d;
+    if (x != 2)
+        fails = fails + 1;
+    x = y = a + b * c;
+    if (x != 13)
+        fails = fails + 1;
+
+    // The comma is below assignments
+    x = 1, y = 2;
+    if (x != 1)
+        fails = fails + 1;
+    if (y != 2)
+        fails = fails + 1;
+    x = (a, b);
+    if (x != 3)
+        fails = fails + 1;
+
+    return fails;
+}
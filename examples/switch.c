@@ -0,0 +1,273 @@
+// Exits with the number of failed checks
+
+// Dense cases, lowered to a jump table
+int dense(int x)
+{
+    switch (x) {
+    case 0:
+        return 10;
+    case 1:
+        return 11;
+    case 2:
+    case 3:
+        return 23;
+    case 5:
+        return 15;
+    case -1:
+        return 9;
+    default:
+        return 99;
+    }
+    return 0;
+}
+
+// Sparse cases, lowered to a compare chain
+int sparse(int x)
+{
+    switch (x) {
+    case 1000000:
+        return 1;
+    case -7:
+        return 2;
+    case 'a':
+        return 3;
+    case 1 << 20:
+        return 4;
+    }
+    return 0;
+}
+
+// Cases fall through to the labels after them
+int fallthrough(int x)
+{
+    int n = 0;
+    switch (x) {
+    case 1:
+        n = n + 1;
+    case 2:
+        n = n + 10;
+    default:
+        n = n + 100;
+    case 3:
+        n = n + 1000;
+    }
+    return n;
+}
+
+// Constant expressions, and values converted to the promoted type
+int constants(char c)
+{
+    switch (c) {
+    case 2 * 3 + 1:
+        return 1;
+    case 'z' - 'a':
+        return 2;
+    case -1:
+        return 3;
+    case 1 ? 40 : 50:
+        return 4;
+    case ~0 - 1:
+        return 5;
+    }
+    return 0;
+}
+
+int wide(long x)
+{
+    switch (x) {
+    case 9000000000:
+        return 1;
+    case -9000000000:
+        return 2;
+    case 9000000001:
+        return 3;
+    case 9000000002:
+        return 4;
+    case 9000000003:
+        return 5;
+    }
+    return 0;
+}
+
+int unsigned_switch(unsigned int x)
+{
+    switch (x) {
+    case 4294967295:
+        return 1;
+    case 0:
+        return 2;
+    case 1:
+        return 3;
+    case 2:
+        return 4;
+    }
+    return 0;
+}
+
+// A state machine, each state returns the next one
+int step(int state, char c)
+{
+    switch (state) {
+    case 0:
+        if (c == '-')
+            return 1;
+    case 1:
+        if (c >= '0' && c <= '9')
+            return 2;
+        return 3;
+    case 2:
+        if (c >= '0' && c <= '9')
+            return 2;
+        return 3;
+    }
+    return 3;
+}
+
+int nested(int a, int b)
+{
+    switch (a) {
+    case 1:
+        switch (b) {
+        case 1:
+            return 11;
+        case 2:
+            return 12;
+        }
+        return 10;
+    case 2:
+        return 20;
+    }
+    return 0;
+}
+
+// Labels after a definition in the body
+int scoped(int x)
+{
+    switch (x) {
+        int y = 5;
+    case 1:
+        y = 7;
+        return y + fallthrough(3);
+    default:
+        return 0;
+    }
+    return 0;
+}
+
+int main()
+{
+    int fails = 0;
+
+    if (dense(0) != 10)
+        fails = fails + 1;
+    if (dense(1) != 11)
+        fails = fails + 1;
+    if (dense(2) != 23)
+        fails = fails + 1;
+    if (dense(3) != 23)
+        fails = fails + 1;
+    if (dense(4) != 99)
+        fails = fails + 1;
+    if (dense(5) != 15)
+        fails = fails + 1;
+    if (dense(-1) != 9)
+        fails = fails + 1;
+    if (dense(-2) != 99)
+        fails = fails + 1;
+    if (dense(6) != 99)
+        fails = fails + 1;
+    if (dense(1000) != 99)
+        fails = fails + 1;
+
+    if (sparse(1000000) != 1)
+        fails = fails + 1;
+    if (sparse(-7) != 2)
+        fails = fails + 1;
+    if (sparse(97) != 3)
+        fails = fails + 1;
+    if (sparse(1048576) != 4)
+        fails = fails + 1;
+    if (sparse(5) != 0)
+        fails = fails + 1;
+
+    if (fallthrough(1) != 1111)
+        fails = fails + 1;
+    if (fallthrough(2) != 1110)
+        fails = fails + 1;
+    if (fallthrough(3) != 1000)
+        fails = fails + 1;
+    if (fallthrough(4) != 1100)
+        fails = fails + 1;
+
+    if (constants(7) != 1)
+        fails = fails + 1;
+    if (constants(25) != 2)
+        fails = fails + 1;
+    if (constants(-1) != 3)
+        fails = fails + 1;
+    if (constants(40) != 4)
+        fails = fails + 1;
+    if (constants(-2) != 5)
+        fails = fails + 1;
+    if (constants(0) != 0)
+        fails = fails + 1;
+
+    long w = 9000000000;
+    if (wide(w) != 1)
+        fails = fails + 1;
+    w = -9000000000;
+    if (wide(w) != 2)
+        fails = fails + 1;
+    w = 9000000003;
+    if (wide(w) != 5)
+        fails = fails + 1;
+    w = 3;
+    if (wide(w) != 0)
+        fails = fails + 1;
+
+    unsigned int u = 4294967295;
+    if (unsigned_switch(u) != 1)
+        fails = fails + 1;
+    u = 2;
+    if (unsigned_switch(u) != 4)
+        fails = fails + 1;
+    u = 3;
+    if (unsigned_switch(u) != 0)
+        fails = fails + 1;
+
+    // "-42" is a number, "4-" isn't
+    int state = 0;
+    state = step(state, '-');
+    state = step(state, '4');
+    state = step(state, '2');
+    if (state != 2)
+        fails = fails + 1;
+    state = 0;
+    state = step(state, '4');
+    state = step(state, '-');
+    if (state != 3)
+        fails = fails + 1;
+
+    if (nested(1, 2) != 12)
+        fails = fails + 1;
+    if (nested(1, 3) != 10)
+        fails = fails + 1;
+    if (nested(2, 1) != 20)
+        fails = fails + 1;
+
+    if (scoped(1) != 1007)
+        fails = fails + 1;
+    if (scoped(2) != 0)
+        fails = fails + 1;
+
+    // Without a matching case or default, nothing in the body runs
+    int x = 5;
+    switch (x) {
+    case 1:
+        x = 0;
+    }
+    if (x != 5)
+        fails = fails + 1;
+
+    return fails;
+}
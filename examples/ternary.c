@@ -0,0 +1,165 @@
+// Exits with the number of failed checks
+#define ROTATE (t = x, x = y, y = z, z = t)
+
+struct point {
+    int x;
+    int y;
+};
+
+// Counts how often it runs
+int count(int value, int *calls)
+{
+    *calls = *calls + 1;
+    return value;
+}
+
+int *pick(int *p, int *calls)
+{
+    *calls = *calls + 1;
+    return p;
+}
+
+int main()
+{
+    int fails = 0;
+
+    // Only the selected arm runs
+    int lcalls = 0;
+    int rcalls = 0;
+    int v = 1 ? count(10, &lcalls) : count(20, &rcalls);
+    if (v != 10)
+        fails = fails + 1;
+    if (lcalls != 1)
+        fails = fails + 1;
+    if (rcalls != 0)
+        fails = fails + 1;
+    v = v > 100 ? count(30, &lcalls) : count(40, &rcalls);
+    if (v != 40)
+        fails = fails + 1;
+    if (lcalls != 1)
+        fails = fails + 1;
+    if (rcalls != 1)
+        fails = fails + 1;
+
+    // The condition runs once
+    int ccalls = 0;
+    v = count(0, &ccalls) ? 1 : 2;
+    if (v != 2)
+        fails = fails + 1;
+    if (ccalls != 1)
+        fails = fails + 1;
+
+    // Side effects in the other arm don't happen
+    int n = 5;
+    v = n ? n : n++;
+    if (n != 5)
+        fails = fails + 1;
+    v = 0 ? n-- : n + 1;
+    if (v != 6)
+        fails = fails + 1;
+    if (n != 5)
+        fails = fails + 1;
+
+    // Right associative, and lower than || but higher than assignment
+    int k = 2;
+    v = k == 1 ? 100 : k == 2 ? 200 : 300;
+    if (v != 200)
+        fails = fails + 1;
+    v = k == 3 ? 100 : k == 4 ? 200 : 300;
+    if (v != 300)
+        fails = fails + 1;
+    v = 0 || k ? 7 : 8;
+    if (v != 7)
+        fails = fails + 1;
+
+    // Pointer arms
+    int a = 3;
+    int b = 4;
+    int *p = a > b ? &a : &b;
+    if (*p != 4)
+        fails = fails + 1;
+    *(a < b ? &a : &b) = 9;
+    if (a != 9)
+        fails = fails + 1;
+    int pcalls = 0;
+    p = k ? pick(&a, &pcalls) : pick(&b, &pcalls);
+    if (*p != 9)
+        fails = fails + 1;
+    if (pcalls != 1)
+        fails = fails + 1;
+    int *null = k ? 0 : &a;
+    if (null != 0)
+        fails = fails + 1;
+
+    // Struct arms are copied
+    struct point s1 = (struct point){ .x = 1, .y = 2 };
+    struct point s2 = (struct point){ .x = 3, .y = 4 };
+    struct point s3 = k ? s2 : s1;
+    if (s3.x != 3)
+        fails = fails + 1;
+    if (s3.y != 4)
+        fails = fails + 1;
+    s2.x = 5;
+    if (s3.x != 3)
+        fails = fails + 1;
+    struct point s4 = k == 0 ? s2 : k == 1 ? s2 : s1;
+    if (s4.x != 1)
+        fails = fails + 1;
+    if (s4.y != 2)
+        fails = fails + 1;
+
+    // Struct arms without an address, in either arm and nested
+    struct point s5 = k ? (struct point){ .x = 7, .y = 8 } : s1;
+    if (s5.x != 7)
+        fails = fails + 1;
+    if (s5.y != 8)
+        fails = fails + 1;
+    struct point s6 = k == 0 ? s1 : k == 2 ? (struct point){ .x = 9, .y = 10 } : s2;
+    if (s6.x != 9)
+        fails = fails + 1;
+    if (s6.y != 10)
+        fails = fails + 1;
+    if (s1.x != 1)
+        fails = fails + 1;
+
+    // Arms are converted to a common type
+    char c = 1;
+    long big = c ? 4000000000 : c;
+    if (big != 4000000000)
+        fails = fails + 1;
+    double d = c ? 1 : 2.5;
+    if (d != 1.0)
+        fails = fails + 1;
+
+    // Comma evaluates left to right and gives the right value
+    int x = 1;
+    int y = 2;
+    int z = 3;
+    int t = 0;
+    v = (x = 5, x + 1);
+    if (v != 6)
+        fails = fails + 1;
+    x = 1;
+    ROTATE;
+    if (x != 2)
+        fails = fails + 1;
+    if (y != 3)
+        fails = fails + 1;
+    if (z != 1)
+        fails = fails + 1;
+
+    // Comma in a for header
+    int sum = 0;
+    int i = 0;
+    int j = 0;
+    for (i = 0, j = 10; i < j; i++, j--)
+        sum += j - i;
+    if (sum != 30)
+        fails = fails + 1;
+    if (i != 5)
+        fails = fails + 1;
+    if (j != 5)
+        fails = fails + 1;
+
+    return fails;
+}
@@ -0,0 +1,101 @@
+// Exits with the number of failed checks
+int neg(int x)
+{
+    return -x;
+}
+
+double dneg(double x)
+{
+    return -x;
+}
+
+int main()
+{
+    int fails = 0;
+
+    int a = 7;
+    int b = -a;
+    if (b != 0 - 7)
+        fails = fails + 1;
+    if (-b != 7)
+        fails = fails + 1;
+    if (+a != 7)
+        fails = fails + 1;
+    if (- -a != 7)
+        fails = fails + 1;
+    if (a - -a != 14)
+        fails = fails + 1;
+    if (-a * -2 != 14)
+        fails = fails + 1;
+    if (neg(a) != -7)
+        fails = fails + 1;
+    if (~a != -8)
+        fails = fails + 1;
+    if (-~a != 8)
+        fails = fails + 1;
+    if (~-a != 6)
+        fails = fails + 1;
+
+    // Unary operators bind tighter than binops, and postfix operators tighter still
+    if (-a + 10 != 3)
+        fails = fails + 1;
+    int i = 2;
+    if (-i++ != -2)
+        fails = fails + 1;
+    if (i != 3)
+        fails = fails + 1;
+
+    // Operands are promoted, so char and short values negate as int
+    char c = 5;
+    int nc = -c;
+    if (nc != -5)
+        fails = fails + 1;
+    char m = -128;
+    if (-m != 128)
+        fails = fails + 1;
+    if (~m != 127)
+        fails = fails + 1;
+
+    // Unsigned negation wraps
+    unsigned int u = 1;
+    if (-u != 4294967295)
+        fails = fails + 1;
+    if (-1u != 4294967295)
+        fails = fails + 1;
+
+    // 2147483648 doesn't fit in int, so it and its negation are long
+    long min = -2147483648;
+    if (min != -2147483647 - 1)
+        fails = fails + 1;
+    if (-2147483648 > 0)
+        fails = fails + 1;
+    int imin = -2147483648;
+    if (imin >= 0)
+        fails = fails + 1;
+    if (imin != -2147483647 - 1)
+        fails = fails + 1;
+    long big = -9000000000;
+    if (-big != 9000000000)
+        fails = fails + 1;
+
+    // Floats flip the sign bit
+    double d = 1.5;
+    if (-d != -1.5)
+        fails = fails + 1;
+    if (-d + 2.0 != 0.5)
+        fails = fails + 1;
+    if (dneg(d) != -1.5)
+        fails = fails + 1;
+    float f = -2.5f;
+    if (-f != 2.5f)
+        fails = fails + 1;
+    if (+f != -2.5f)
+        fails = fails + 1;
+    if (1.0 / -0.0 > 0.0)
+        fails = fails + 1;
+    double z = 0.0;
+    if (1.0 / -z > 0.0)
+        fails = fails + 1;
+
+    return fails;
+}
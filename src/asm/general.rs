@@ -1,6 +1,6 @@
 use super::instruction::AsmArg;
 use super::util;
-use super::Gen;
+use super::{Gen, GotoLabel, JumpTargets, Switch};
 use crate::cdefs::{CFdef, CVardef};
 use crate::error::{Error, ErrorType};
 use crate::node::{Dtype, DtypeVariant, Node, NodeVariant};
@@ -23,21 +23,43 @@ impl Gen {
         let prev_layer: ScopeLayer = self.scope.pop_layer();
 
         self.scope.push_layer();
-        let NodeVariant::Fdef { name, body, rtype: _, .. } = n.variant.as_ref() else { unreachable!() };
+        // rbx is saved below rbp
+        self.scope.stack_offset_change(-8);
+        let NodeVariant::Fdef { name, body, rtype, .. } = n.variant.as_ref() else { unreachable!() };
+        self.rtype = rtype.clone();
 
         // Push params into scope so function body can access them
         self.scope.push_fdef(n)?;
         let fdef: CFdef = self.scope.find_fdef(name, n.line)?.clone();
         let NodeVariant::Fdef { params, .. } = fdef.node.variant.as_ref() else { unreachable!() };
+        // Params passed in registers are copied onto the stack
+        let mut spills: String = String::new();
         for (i, param) in params.clone().iter().enumerate() {
-            self.scope
-                .push_cvardef(&CVardef::new(param, fdef.param_stack_offsets[i]));
+            let offset: i32 = match &fdef.param_registers[i] {
+                Some(reg) => {
+                    let dtype: Dtype = param.dtype(&self.scope)?;
+                    self.scope.stack_offset_change_n(param, -1)?;
+                    let offset: i32 = self.scope.stack_offset();
+                    spills.push_str(&self.asm_extend_stack(dtype.num_bytes(&self.scope)?));
+                    spills.push_str(&self.asm_mov(AsmArg::Stack(&dtype, offset), AsmArg::Register(reg), false)?);
+                    offset
+                }
+                None => fdef.param_stack_offsets[i],
+            };
+            self.scope.push_cvardef(&CVardef::new(param, offset));
         }
 
         let res: String = if matches!(body.variant.as_ref(), NodeVariant::Noop) {
+            if !self.declared.contains(name) {
+                self.declared.push(name.clone());
+            }
             String::new()
         } else {
-            format!("\n\t; [fdef] begin\n{}:\n\tpush rbp\n\tmov rbp, rsp\n\n\t; [fdef] body{}\n\t; [fdef] end body\n\t; [fdef] backup return\n\tmov rsp, rbp\n\tpop rbp\n\tret\n", name, self.gen_expr(body)?)
+            self.defined.push(name.clone());
+            self.labels.clear();
+            let body: String = self.gen_expr(body)?;
+            self.check_labels()?;
+            format!("\n\t; [fdef] begin\n{}:\n\tpush rbp\n\tmov rbp, rsp\n\tpush rbx\n\t; [fdef] params{}\n\n\t; [fdef] body{}\n\t; [fdef] end body\n\t; [fdef] backup return{}", name, spills, body, self.asm_epilogue())
         };
 
         self.scope.pop_layer();
@@ -49,11 +71,13 @@ impl Gen {
 
     pub fn gen_return(&mut self, n: &Node) -> Result<String, Error> {
         let NodeVariant::Return { value } = n.variant.as_ref() else { unreachable!() };
-        let reg: String = util::register('a', value, self)?;
+        // Floats are returned in xmm0, like System V
+        let value: Node = self.convert_for(value, &self.rtype.clone())?;
+        let reg: String = util::register('a', &value, self)?;
         Ok(format!(
             "\n\t; [return] {}{}",
-            self.asm_mov(AsmArg::Register(reg.as_str()), AsmArg::Node(value), true)?,
-            "\n\tmov rsp, rbp\n\tpop rbp\n\tret\n"
+            self.asm_mov(AsmArg::Register(reg.as_str()), AsmArg::Node(&value), true)?,
+            self.asm_epilogue()
         ))
     }
 
@@ -65,7 +89,7 @@ impl Gen {
         let mut passed_args: Vec<Node> = Vec::new();
 
         // Get params
-        let fdef: CFdef = self.scope.find_fdef(name, n.line)?.clone();
+        let fdef: CFdef = self.scope.find_fdef(name, n.line).map_err(|e| e.or_span(n.span))?.clone();
         let NodeVariant::Fdef { params, .. } = fdef.node.variant.as_ref() else { unreachable!() };
 
         // Check if equal
@@ -73,24 +97,60 @@ impl Gen {
             return Err(Error::new(
                 ErrorType::FunctionArgParamMismatch(name.as_str(), args.len(), params.len()),
                 n.line,
-            ));
+            )
+            .with_span(match (args.first(), args.last()) {
+                (Some(first), Some(last)) => first.span.to(last.span),
+                _ => n.span,
+            }));
         }
 
         // Fill in argument values to be passed
         for i in 0..args.len() {
             let mut param: Node = params[i].clone();
-            let NodeVariant::Vardef { value, dtype: _, .. } = param.variant.as_mut() else { unreachable!() };
+            let NodeVariant::Vardef { var, value, dtype: _ } = param.variant.as_mut() else { unreachable!() };
             *value = args[i].clone();
+            // Unnamed, so later args still see the caller's variables
+            let NodeVariant::Var { name } = var.variant.as_mut() else { unreachable!() };
+            name.clear();
             passed_args.push(param);
         }
 
+        // Args are evaluated onto the stack first, so evaluating one can't clobber the
+        // registers or stack slots of the others
         res.push_str("\n\t; [fcall] push args");
-        // Push in reverse order
-        // TODO args can potentially modify the stack, which will mess up
-        // parameter access inside the function, since the passed args will
-        // be separated by the preparation process.
-        for arg in passed_args.iter().rev() {
+        let mut evaluated: Vec<(Dtype, i32)> = Vec::new();
+        for arg in passed_args.iter() {
             res.push_str(self.gen_vardef(arg)?.as_str());
+            evaluated.push((arg.dtype(&self.scope)?, self.scope.stack_offset()));
+        }
+
+        // Stack args are copied below the others in 8 byte slots, the first one at rsp
+        let mut slots: i32 = 0;
+        for (i, (dtype, _)) in evaluated.iter().enumerate() {
+            if fdef.param_registers[i].is_none() {
+                slots += (dtype.num_bytes(&self.scope)? + 7) / 8 * 8;
+            }
+        }
+        // Padded above the slots so rsp is 16 byte aligned at the call, like System V requires
+        let area: i32 = slots + (self.scope.stack_offset() - slots).rem_euclid(16);
+        if area > 0 {
+            res.push_str("\n\t; [fcall] args to stack");
+            self.scope.stack_offset_change(-area);
+            res.push_str(&self.asm_extend_stack(area));
+        }
+        let mut slot: i32 = self.scope.stack_offset();
+        for (i, (dtype, offset)) in evaluated.iter().enumerate() {
+            if fdef.param_registers[i].is_none() {
+                res.push_str(&self.asm_mov(AsmArg::Stack(dtype, slot), AsmArg::Stack(dtype, *offset), false)?);
+                slot += (dtype.num_bytes(&self.scope)? + 7) / 8 * 8;
+            }
+        }
+
+        res.push_str("\n\t; [fcall] args to registers");
+        for (i, (dtype, offset)) in evaluated.iter().enumerate() {
+            if let Some(reg) = &fdef.param_registers[i] {
+                res.push_str(&self.asm_mov(AsmArg::Register(reg), AsmArg::Stack(dtype, *offset), false)?);
+            }
         }
 
         // Only the generated assembly is needed, side effect of variables pushed
@@ -100,6 +160,11 @@ impl Gen {
             self.scope.pop_vardef();
         }
 
+        // Variadic callees read the number of xmm args from al. Prototypes can't say
+        // whether a function is variadic, so it's always set.
+        let n_floats: usize = fdef.param_registers.iter().flatten().filter(|x| x.starts_with("xmm")).count();
+        res.push_str(format!("\n\tmov eax, {}", n_floats).as_str());
+
         // Same between x86 and x86_64
         res.push_str(format!("\n\tcall {}", name).as_str());
         Ok(res)
@@ -117,20 +182,37 @@ impl Gen {
     }
 
     pub fn gen_if(&mut self, n: &Node) -> Result<String, Error> {
-        let NodeVariant::If { cond, body } = n.variant.as_ref() else { unreachable!() };
+        let NodeVariant::If { cond, body, else_body } = n.variant.as_ref() else { unreachable!() };
 
         // Evaluate cond
-        let zero_node: Node = Node::new(NodeVariant::Int { value: 0 }, n.line);
+        let zero_node: Node = Node::new(NodeVariant::Int { value: 0, dtype: DtypeVariant::Int }, n.line);
         let cmp: String = self.asm_cmp(AsmArg::Node(cond), AsmArg::Node(&zero_node))?;
 
         //     <body>
+        //     jmp .Lx
+        // .Lx_else:
+        //     <else body>
         // .Lx:
         //     <rest of the program>
-        // If cmp is equal (cond is true) then skip if body, or jump to .Lx
+        // If cmp is equal (cond is false) then skip if body, or jump to .Lx_else
+        // A skipped body may have grown the stack at compile time, so rsp is resynced
         let label: usize = self.label;
-        let body_and_jmp: String =
-            format!("\n\tje .L{}{}\n.L{}:", label, self.gen_expr(body)?, label,);
         self.label += 1;
+        let body_and_jmp: String = if matches!(else_body.variant.as_ref(), NodeVariant::Noop) {
+            format!("\n\tje .L{}{}\n.L{}:{}", label, self.gen_expr(body)?, label, self.asm_sync_stack())
+        } else {
+            format!(
+                "\n\tje .L{}_else{}\n\tjmp .L{}\n.L{}_else:{}{}\n.L{}:{}",
+                label,
+                self.gen_expr(body)?,
+                label,
+                label,
+                self.asm_sync_stack(),
+                self.gen_expr(else_body)?,
+                label,
+                self.asm_sync_stack()
+            )
+        };
 
         Ok(format!("\n\t; [if]{}{}\n\t; [end if]", cmp, body_and_jmp,))
     }
@@ -138,44 +220,276 @@ impl Gen {
     pub fn gen_while(&mut self, n: &Node) -> Result<String, Error> {
         let NodeVariant::While { cond, body } = n.variant.as_ref() else { unreachable!() };
 
-        // TODO Currently this behaves like a do-while loop
         /*
            .Lx:
+                cmp cond, 0
+                je .Lx_end
                 <while body>
+                jmp .Lx
+           .Lx_end:
+        */
+        let label: usize = self.label;
+        self.label += 1;
+
+        let zero_node: Node = Node::new(NodeVariant::Int { value: 0, dtype: DtypeVariant::Int }, n.line);
+        let top: String = format!("\n\t; [while]\n.L{}:{}", label, self.asm_sync_stack());
+        let cmp: String = self.asm_cmp(AsmArg::Node(cond), AsmArg::Node(&zero_node))?;
+        let body: String = self.gen_loop_body(body, format!(".L{}_end", label), Some(format!(".L{}", label)))?;
+        Ok(format!(
+            "{}{}\n\tje .L{}_end{}\n\tjmp .L{}\n.L{}_end:{}\n\t; [end while]",
+            top,
+            cmp,
+            label,
+            body,
+            label,
+            label,
+            self.asm_sync_stack(),
+        ))
+    }
+
+    pub fn gen_do_while(&mut self, n: &Node) -> Result<String, Error> {
+        let NodeVariant::DoWhile { body, cond } = n.variant.as_ref() else { unreachable!() };
+
+        /*
+           .Lx:
+                <body>
+           .Lx_cond:
                 cmp cond, 0
                 jne .Lx
+           .Lx_end:
         */
         let label: usize = self.label;
         self.label += 1;
 
-        let zero_node: Node = Node::new(NodeVariant::Int { value: 0 }, n.line);
+        let zero_node: Node = Node::new(NodeVariant::Int { value: 0, dtype: DtypeVariant::Int }, n.line);
+        let top: String = format!("\n\t; [do while]\n.L{}:{}", label, self.asm_sync_stack());
+        let body: String = self.gen_loop_body(body, format!(".L{}_end", label), Some(format!(".L{}_cond", label)))?;
+        let cond_label: String = format!("\n.L{}_cond:{}", label, self.asm_sync_stack());
         Ok(format!(
-            "\n\t; [while]\n.L{}:{}\n\t{}\n\tjne .L{}\n\t; [end while]",
-            label,
-            self.gen_expr(body)?,
+            "{}{}{}{}\n\tjne .L{}\n.L{}_end:{}\n\t; [end do while]",
+            top,
+            body,
+            cond_label,
             self.asm_cmp(AsmArg::Node(cond), AsmArg::Node(&zero_node))?,
             label,
+            label,
+            self.asm_sync_stack(),
+        ))
+    }
+
+    /// Body of a loop or switch, where break and continue go to the given labels
+    fn gen_loop_body(&mut self, body: &Node, break_label: String, continue_label: Option<String>) -> Result<String, Error> {
+        self.jumps.push(JumpTargets { break_label, continue_label });
+        let res: Result<String, Error> = self.gen_expr(body);
+        self.jumps.pop();
+        res
+    }
+
+    /// break and continue
+    pub fn gen_break(&mut self, n: &Node) -> Result<String, Error> {
+        let target: Option<&String> = match n.variant.as_ref() {
+            NodeVariant::Break => self.jumps.last().map(|x| &x.break_label),
+            _ => self.jumps.iter().rev().find_map(|x| x.continue_label.as_ref()),
+        };
+        let Some(target) = target else {
+            let etype: ErrorType = match n.variant.as_ref() {
+                NodeVariant::Break => ErrorType::BreakOutsideLoop,
+                _ => ErrorType::ContinueOutsideLoop,
+            };
+            return Err(Error::new(etype, n.line).with_span(n.span));
+        };
+
+        Ok(format!("\n\tjmp {}", target))
+    }
+
+    pub fn gen_goto(&mut self, n: &Node) -> Result<String, Error> {
+        let NodeVariant::Goto { name } = n.variant.as_ref() else { unreachable!() };
+        let i: usize = self.find_label(name);
+        if self.labels[i].used.is_none() {
+            self.labels[i].used = Some((n.line, n.span));
+        }
+
+        Ok(format!("\n\tjmp {}", self.labels[i].asm_label))
+    }
+
+    /// Gotos can jump past vardefs, so rsp is resynced after the label
+    pub fn gen_label(&mut self, n: &Node) -> Result<String, Error> {
+        let NodeVariant::Label { name } = n.variant.as_ref() else { unreachable!() };
+        let i: usize = self.find_label(name);
+        if let Some(span) = self.labels[i].defined {
+            return Err(Error::new(ErrorType::DuplicateLabel(name), n.line)
+                .with_span(n.span)
+                .with_note("Previously defined here.", span));
+        }
+        self.labels[i].defined = Some(n.span);
+
+        Ok(format!("\n{}:{}", self.labels[i].asm_label, self.asm_sync_stack()))
+    }
+
+    /// Index of the label in the current function, added if it wasn't seen yet
+    fn find_label(&mut self, name: &str) -> usize {
+        if let Some(i) = self.labels.iter().position(|x| x.name == name) {
+            return i;
+        }
+
+        self.labels.push(GotoLabel {
+            name: name.to_string(),
+            asm_label: format!(".L{}_{}", self.label, name),
+            defined: None,
+            used: None,
+        });
+        self.label += 1;
+        self.labels.len() - 1
+    }
+
+    /// Every label jumped to in the function is defined
+    fn check_labels(&self) -> Result<(), Error> {
+        for label in &self.labels {
+            if let (None, Some((line, span))) = (label.defined, label.used) {
+                return Err(Error::new(ErrorType::UndefinedLabel(label.name.as_str()), line).with_span(span));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn gen_for(&mut self, n: &Node) -> Result<String, Error> {
+        let NodeVariant::For { init, cond, inc, body } = n.variant.as_ref() else { unreachable!() };
+
+        /*
+                <init>
+           .Lx:
+                cmp cond, 0
+                je .Lx_end
+                <for body>
+           .Lx_inc:
+                <inc>
+                jmp .Lx
+           .Lx_end:
+        */
+        let label: usize = self.label;
+        self.label += 1;
+
+        let mut res: String = format!("\n\t; [for]{}\n.L{}:{}", self.gen_expr(init)?, label, self.asm_sync_stack());
+        // An empty condition loops forever
+        if !matches!(cond.variant.as_ref(), NodeVariant::Noop) {
+            let zero_node: Node = Node::new(NodeVariant::Int { value: 0, dtype: DtypeVariant::Int }, n.line);
+            res.push_str(self.asm_cmp(AsmArg::Node(cond), AsmArg::Node(&zero_node))?.as_str());
+            res.push_str(format!("\n\tje .L{}_end", label).as_str());
+        }
+        let body: String = self.gen_loop_body(body, format!(".L{}_end", label), Some(format!(".L{}_inc", label)))?;
+        res.push_str(body.as_str());
+        res.push_str(format!("\n.L{}_inc:{}", label, self.asm_sync_stack()).as_str());
+        res.push_str(self.gen_expr(inc)?.as_str());
+        res.push_str(format!("\n\tjmp .L{}\n.L{}_end:{}\n\t; [end for]", label, label, self.asm_sync_stack()).as_str());
+
+        Ok(res)
+    }
+
+    pub fn gen_switch(&mut self, n: &Node) -> Result<String, Error> {
+        let NodeVariant::Switch { cond, body } = n.variant.as_ref() else { unreachable!() };
+
+        let dtype: Dtype = cond.dtype(&self.scope)?;
+        if !dtype.is_integer() {
+            return Err(Error::new(ErrorType::SwitchNotInteger(dtype), n.line).with_span(cond.span));
+        }
+        let dtype: Dtype = dtype.promoted();
+        let cond: Node = cond.cast(&dtype, &self.scope)?;
+        let reg: String = dtype.register('a', &self.scope)?;
+        let value: String = self.asm_mov(AsmArg::Register(reg.as_str()), AsmArg::Node(&cond), true)?;
+
+        /*
+                <value in the a register>
+                <jump table or compare chain>
+                <switch body, with .Lx_caseN and .Lx_default labels>
+           .Lx_end:
+        */
+        // The body is generated first, its labels give the cases to jump to
+        let label: usize = self.label;
+        self.label += 1;
+        self.switches.push(Switch { label, dtype, cases: Vec::new(), default: None });
+        let body: Result<String, Error> = self.gen_loop_body(body, format!(".L{}_end", label), None);
+        let switch: Switch = self.switches.pop().unwrap();
+        let body: String = body?;
+
+        // Without a matching case, go to default or past the body
+        let fallback: String = match switch.default {
+            Some(_) => format!(".L{}_default", label),
+            None => format!(".L{}_end", label),
+        };
+        let dispatch: String = if switch.is_dense() {
+            switch.jump_table(&fallback, &self.scope)?
+        } else {
+            switch.compare_chain(&fallback, &self.scope)?
+        };
+
+        Ok(format!(
+            "\n\t; [switch]{}{}{}\n.L{}_end:{}\n\t; [end switch]",
+            value,
+            dispatch,
+            body,
+            label,
+            self.asm_sync_stack()
         ))
     }
 
+    /// Labels can be jumped to past vardefs, so rsp is resynced after them
+    pub fn gen_case(&mut self, n: &Node) -> Result<String, Error> {
+        let NodeVariant::Case { value } = n.variant.as_ref() else { unreachable!() };
+        let is_default: bool = matches!(value.variant.as_ref(), NodeVariant::Noop);
+        let Some(switch) = self.switches.last() else {
+            let keyword: &str = if is_default { "default" } else { "case" };
+            return Err(Error::new(ErrorType::CaseOutsideSwitch(keyword), n.line).with_span(n.span));
+        };
+        let label: usize = switch.label;
+
+        if is_default {
+            if let Some(span) = switch.default {
+                return Err(Error::new(ErrorType::DuplicateDefault, n.line)
+                    .with_span(n.span)
+                    .with_note("Previous default is here.", span));
+            }
+            self.switches.last_mut().unwrap().default = Some(n.span);
+            return Ok(format!("\n.L{}_default:{}", label, self.asm_sync_stack()));
+        }
+
+        // Case values are converted to the promoted type of the controlling expression
+        let Some(x) = value.int_const() else {
+            return Err(Error::new(ErrorType::CaseNotConstant, n.line).with_span(value.span));
+        };
+        let x: Node = Node::new(NodeVariant::Int { value: x as u64, dtype: DtypeVariant::Long }, n.line)
+            .cast(&switch.dtype, &self.scope)?;
+        let NodeVariant::Int { value: x, .. } = x.variant.as_ref() else { unreachable!() };
+        if let Some((_, span)) = switch.cases.iter().find(|(y, _)| y == x) {
+            return Err(Error::new(ErrorType::DuplicateCase(switch.value_repr(*x)), n.line)
+                .with_span(n.span)
+                .with_note("Previously used here.", *span));
+        }
+
+        let i: usize = switch.cases.len();
+        self.switches.last_mut().unwrap().cases.push((*x, n.span));
+        Ok(format!("\n.L{}_case{}:{}", label, i, self.asm_sync_stack()))
+    }
+
     pub fn gen_vardef(&mut self, n: &Node) -> Result<String, Error> {
         // First prepare the value before pushing vardef
         // onto stack to prevent holes in the stack.
         let NodeVariant::Vardef { value, .. } = n.variant.as_ref() else { unreachable!() };
-        let value_dtype: Dtype = value.dtype(&self.scope)?;
         let n_dtype: Dtype = n.dtype(&self.scope)?;
+        let value: Node = self.convert_for(value, &n_dtype)?;
+        let value_dtype: Dtype = value.dtype(&self.scope)?;
         if value_dtype != n_dtype {
             return Err(Error::new(
                 ErrorType::AssignTypeMismatch(n_dtype, value_dtype),
                 n.line,
-            ));
+            )
+            .with_span(value.span));
         }
-        let mut res: String = self.gen_expr(value)?;
+        let mut res: String = self.gen_expr(&value)?;
 
         res.push_str("\n\t; [vardef] assign value");
         self.scope.stack_offset_change_n(n, -1)?;
         self.scope.push_vardef(n);
-        res.push_str(&self.gen_stack_push(value)?);
+        res.push_str(&self.gen_stack_push(&value)?);
 
         Ok(res)
     }
@@ -186,6 +500,16 @@ impl Gen {
     /// * gen_expr the value getting pushed onto the stack if needed, this function won't do it.
     pub fn gen_stack_push(&mut self, pushed: &Node) -> Result<String, Error> {
         Ok(match pushed.dtype(&self.scope)?.variant {
+            // gen_ternary leaves the selected struct's address in rax
+            DtypeVariant::Struct { .. } if matches!(pushed.strip(&self.scope)?.variant.as_ref(), NodeVariant::Ternary { .. }) => {
+                let nbytes: i32 = pushed.dtype(&self.scope)?.num_bytes(&self.scope)?;
+                format!(
+                    "{}\n\tmov rsi, rax\n\tlea rdi, [rbp{:+}]\n\tmov rcx, {}\n\trep movsb",
+                    self.asm_extend_stack(nbytes),
+                    self.scope.stack_offset(),
+                    nbytes
+                )
+            }
             // gen_init_list pushes variables onto the stack
             DtypeVariant::Struct { .. } => {
                 self.scope.stack_offset_change_n(pushed, 1)?;
@@ -1,7 +1,7 @@
 use crate::asm::Gen;
 use crate::error::Error;
 use crate::lexer::TokenType;
-use crate::node::{Dtype, Node};
+use crate::node::{Dtype, DtypeVariant, Node, NodeVariant};
 
 pub enum AsmArg<'a> {
     Node(&'a Node),
@@ -27,6 +27,17 @@ impl<'a> AsmArg<'a> {
         }
     }
 
+    /// 64-bit immediates outside the sign-extended 32-bit range only go into registers
+    fn is_wide_immediate(&self) -> bool {
+        let AsmArg::Node(n) = self else { return false };
+        let NodeVariant::Int { value, dtype } = n.variant.as_ref() else { return false };
+        match dtype.max_value() {
+            x if x <= u32::MAX as u64 => false,
+            _ if dtype.is_unsigned() => *value > i32::MAX as u64,
+            _ => i32::try_from(*value as i64).is_err(),
+        }
+    }
+
     fn gen_expr_if_needed(&self, gen: &mut Gen) -> Result<String, Error> {
         if let AsmArg::Node(n) = self {
             gen.gen_expr(n)
@@ -36,6 +47,22 @@ impl<'a> AsmArg<'a> {
     }
 }
 
+/// mov, or movss / movsd if an operand is an xmm register
+pub fn mov_instruction(dest: &str, src: &str) -> &'static str {
+    if !dest.starts_with("xmm") && !src.starts_with("xmm") {
+        "mov"
+    } else if dest.starts_with("DWORD") || src.starts_with("DWORD") {
+        "movss"
+    } else {
+        "movsd"
+    }
+}
+
+/// ss for float and sd for double operations
+pub fn float_suffix(dtype: &Dtype) -> &'static str {
+    if dtype.variant == DtypeVariant::Float { "ss" } else { "sd" }
+}
+
 #[cfg(target_arch = "x86_64")]
 impl Gen {
     pub fn asm_mov(&mut self, dest: AsmArg, src: AsmArg, gen_exprs: bool) -> Result<String, Error> {
@@ -57,14 +84,14 @@ impl Gen {
             return Ok(exprs);
         }
 
-        let src_to_dest: String = if dest_repr.contains('[') && src_repr.contains('[') {
+        let src_to_dest: String = if dest_repr.contains('[') && (src_repr.contains('[') || src.is_wide_immediate()) {
             // Avoid mem to mem by moving to a register first
             let reg: String = dest.associated_register(self, 'b')?;
-            let src_to_reg: String = format!("\n\tmov {}, {}", reg, src_repr);
-            let reg_to_dest: String = format!("\n\tmov {}, {}", dest_repr, reg);
+            let src_to_reg: String = format!("\n\t{} {}, {}", mov_instruction(&reg, &src_repr), reg, src_repr);
+            let reg_to_dest: String = format!("\n\t{} {}, {}", mov_instruction(&dest_repr, &reg), dest_repr, reg);
             format!("{}{}", src_to_reg, reg_to_dest)
         } else {
-            format!("\n\tmov {}, {}", dest_repr, src_repr)
+            format!("\n\t{} {}, {}", mov_instruction(&dest_repr, &src_repr), dest_repr, src_repr)
         };
 
         Ok(format!("{}{}", exprs, src_to_dest))
@@ -72,46 +99,130 @@ impl Gen {
 
     /// Result in eax
     pub fn asm_cmp(&mut self, a: AsmArg, b: AsmArg) -> Result<String, Error> {
+        if let (AsmArg::Node(na), AsmArg::Node(nb)) = (&a, &b) {
+            if na.dtype(&self.scope)?.is_float() || nb.dtype(&self.scope)?.is_float() {
+                return self.asm_float_cmp(na, nb);
+            }
+        }
+
         let exprs: String = format!(
             "{}{}",
             a.gen_expr_if_needed(self)?,
             b.gen_expr_if_needed(self)?,
         );
 
-        let a_repr: String = a.repr(self)?;
-        let b_repr: String = b.repr(self)?;
+        let mut a_repr: String = a.repr(self)?;
+        let mut b_repr: String = b.repr(self)?;
+
+        // cmp can't take an immediate first
+        let mut imm_to_reg: String = String::new();
+        if matches!(&a, AsmArg::Node(n) if matches!(n.variant.as_ref(), NodeVariant::Int { .. } | NodeVariant::Char { .. })) {
+            let reg: String = a.associated_register(self, 'a')?;
+            imm_to_reg = format!("\n\tmov {}, {}", reg, a_repr);
+            a_repr = reg;
+        }
+
+        // cmp only takes 32-bit immediates, and at most one memory operand
+        let mut wide_to_reg: String = String::new();
+        if b.is_wide_immediate() {
+            wide_to_reg = format!("\n\tmov rbx, {}", b_repr);
+            b_repr = String::from("rbx");
+        } else if a_repr.contains('[') && b_repr.contains('[') {
+            let reg: String = b.associated_register(self, 'b')?;
+            wide_to_reg = format!("\n\tmov {}, {}", reg, b_repr);
+            b_repr = reg;
+        }
 
         #[allow(clippy::format_in_format_args)]
         Ok(format!(
-            "\n\t; [cmp]{}{}\n\t; [end cmp]",
+            "\n\t; [cmp]{}{}{}{}\n\t; [end cmp]",
             exprs,
+            imm_to_reg,
+            wide_to_reg,
             format!("\n\tcmp {}, {}", a_repr, b_repr)
         ))
     }
 
+    /// Sets the flags like an unsigned cmp, so below and above jumps follow
+    fn asm_float_cmp(&mut self, a: &Node, b: &Node) -> Result<String, Error> {
+        let dtype: Dtype = Dtype::common(&a.dtype(&self.scope)?, &b.dtype(&self.scope)?);
+        let (a, b): (Node, Node) = (a.cast(&dtype, &self.scope)?, b.cast(&dtype, &self.scope)?);
+        let exprs: String = format!("{}{}", self.gen_expr(&a)?, self.gen_expr(&b)?);
+
+        let b_to_reg: String = self.asm_mov(AsmArg::Register("xmm1"), AsmArg::Node(&b), false)?;
+        let a_to_reg: String = self.asm_mov(AsmArg::Register("xmm0"), AsmArg::Node(&a), false)?;
+
+        // NaN sets ZF, PF and CF. ZF is cleared so it's unequal to everything,
+        // and CF stays set so the ja and jae used for floats are false.
+        let label: usize = self.label;
+        self.label += 1;
+        Ok(format!(
+            "\n\t; [cmp]{}{}{}\n\tucomi{} xmm0, xmm1\n\tjnp .L{}_ordered\n\ttest rsp, rsp\n\tstc\n.L{}_ordered:\n\t; [end cmp]",
+            exprs,
+            b_to_reg,
+            a_to_reg,
+            float_suffix(&dtype),
+            label,
+            label
+        ))
+    }
+
     pub fn asm_extend_stack(&self, nbytes: i32) -> String {
         format!("\n\tsub rsp, {}", nbytes)
     }
 
+    /// Return from a function. rbx is callee-saved in System V, so it's restored from
+    /// where the prologue pushed it.
+    pub fn asm_epilogue(&self) -> String {
+        String::from("\n\tmov rbx, QWORD [rbp-8]\n\tmov rsp, rbp\n\tpop rbp\n\tret\n")
+    }
+
+    /// Point rsp at the current stack offset, after a jump from code that grew the stack differently
+    pub fn asm_sync_stack(&self) -> String {
+        format!("\n\tlea rsp, [rbp{:+}]", self.scope.stack_offset())
+    }
+
     /// a and b should be Nodes
     pub fn asm_arithmetic(&mut self, a: AsmArg, b: AsmArg, op: TokenType) -> Result<String, Error> {
         // let expr_a: String = a.gen_expr_if_needed(self)?;
         // let expr_b: String = b.gen_expr_if_needed(self)?;
 
-        let reg_a: String = a.associated_register(self, 'a')?;
-        let reg_b: String = b.associated_register(self, 'b')?;
-
         let AsmArg::Node(na) = a else { unreachable!() };
         let AsmArg::Node(nb) = b else { unreachable!() };
+        let shift: bool = matches!(op, TokenType::Shl | TokenType::Shr);
+        let (na, nb): (Node, Node) = if shift {
+            // Operands are promoted separately, the count goes in cl
+            (
+                na.cast(&na.dtype(&self.scope)?.promoted(), &self.scope)?,
+                nb.cast(&nb.dtype(&self.scope)?.promoted(), &self.scope)?,
+            )
+        } else {
+            self.convert_operands(na, nb)?
+        };
+        let (a, b): (AsmArg, AsmArg) = (AsmArg::Node(&na), AsmArg::Node(&nb));
+
+        let reg_a: String = a.associated_register(self, 'a')?;
+        let reg_b: String = b.associated_register(self, if shift { 'c' } else { 'b' })?;
+        let reg_d: String = a.associated_register(self, 'd')?;
+        let unsigned: bool = na.dtype(&self.scope)?.variant.is_unsigned();
+        let float: bool = na.dtype(&self.scope)?.is_float();
+        let suffix: &str = float_suffix(&na.dtype(&self.scope)?);
+        // Sign extend rax into rdx:rax, or clear rdx for unsigned division
+        let extend: &str = match (unsigned, na.dtype(&self.scope)?.num_bytes(&self.scope)?) {
+            (true, _) => "xor edx, edx",
+            (false, 8) => "cqo",
+            (false, _) => "cdq",
+        };
+
         let a_expr: String = a.gen_expr_if_needed(self)?;
-        self.scope.stack_offset_change_n(na, -1)?;
+        self.scope.stack_offset_change_n(&na, -1)?;
         let aoffset: i32 = self.scope.stack_offset();
-        let a_to_stack: String = self.gen_stack_push(na)?;
+        let a_to_stack: String = self.gen_stack_push(&na)?;
 
         let b_expr: String = b.gen_expr_if_needed(self)?;
-        self.scope.stack_offset_change_n(nb, -1)?;
+        self.scope.stack_offset_change_n(&nb, -1)?;
         let boffset: i32 = self.scope.stack_offset();
-        let b_to_stack: String = self.gen_stack_push(nb)?;
+        let b_to_stack: String = self.gen_stack_push(&nb)?;
 
         let astack_to_reg: String = self.asm_mov(
             AsmArg::Register(reg_a.as_str()),
@@ -135,10 +246,26 @@ impl Gen {
             astack_to_reg,
             bstack_to_reg,
             match op {
+                TokenType::Plus if float => format!("add{} {}, {}", suffix, reg_a, reg_b),
+                TokenType::Minus if float => format!("sub{} {}, {}", suffix, reg_a, reg_b),
+                TokenType::Star if float => format!("mul{} {}, {}", suffix, reg_a, reg_b),
+                TokenType::Div if float => format!("div{} {}, {}", suffix, reg_a, reg_b),
                 TokenType::Plus => format!("add {}, {}", reg_a, reg_b),
                 TokenType::Minus => format!("sub {}, {}", reg_a, reg_b),
-                TokenType::Star => format!("mul {}", reg_b),
-                TokenType::Div => format!("div {}", reg_b),
+                TokenType::Star if unsigned => format!("mul {}", reg_b),
+                TokenType::Star => format!("imul {}, {}", reg_a, reg_b),
+                // Division of rdx:rax, the remainder is left in rdx
+                TokenType::Div if unsigned => format!("{}\n\tdiv {}", extend, reg_b),
+                TokenType::Div => format!("{}\n\tidiv {}", extend, reg_b),
+                TokenType::Percent if unsigned => format!("{}\n\tdiv {}\n\tmov {}, {}", extend, reg_b, reg_a, reg_d),
+                TokenType::Percent => format!("{}\n\tidiv {}\n\tmov {}, {}", extend, reg_b, reg_a, reg_d),
+                TokenType::Amp => format!("and {}, {}", reg_a, reg_b),
+                TokenType::Pipe => format!("or {}, {}", reg_a, reg_b),
+                TokenType::Caret => format!("xor {}, {}", reg_a, reg_b),
+                TokenType::Shl => format!("shl {}, cl", reg_a),
+                // Arithmetic shift keeps the sign of signed values
+                TokenType::Shr if unsigned => format!("shr {}, cl", reg_a),
+                TokenType::Shr => format!("sar {}, cl", reg_a),
                 _ => unreachable!(),
             },
         ))
@@ -148,6 +275,7 @@ impl Gen {
     /// For example, given the operation cmp a, b, assuming param jmp = je:
     /// * If a == b, zf_conditional sets eax to 1
     /// * If a != b, zf_conditional sets eax to 0
+    ///
     /// jmp = je, jne, etc.
     /// If you are comparing with a zero node, you most likely want jmp = jne.
     pub fn asm_zf_conditional(&mut self, result_reg: &str, jmp: &str) -> String {
@@ -6,21 +6,145 @@ mod util;
 use crate::cdefs::CVardef;
 use crate::error::Error;
 use crate::lexer::TokenType;
-use crate::node::{Dtype, Node, NodeVariant};
+use crate::node::{Dtype, DtypeVariant, Node, NodeVariant};
 use crate::scope::Scope;
+use crate::span::Span;
 
 pub struct Gen {
     scope: Scope,
     data: String,
     label: usize,
+    /// Emit _start if this unit defines main
+    entry_stub: bool,
+    /// Functions defined in this unit
+    defined: Vec<String>,
+    /// Functions only declared in this unit
+    declared: Vec<String>,
+    /// Return type of the function being generated
+    rtype: Dtype,
+    /// Switches being generated, innermost last
+    switches: Vec<Switch>,
+    /// Loops and switches being generated, innermost last
+    jumps: Vec<JumpTargets>,
+    /// Goto labels of the function being generated
+    labels: Vec<GotoLabel>,
+}
+
+/// Where break and continue in a loop or switch go
+struct JumpTargets {
+    break_label: String,
+    /// None for switches, continue goes to the loop around them
+    continue_label: Option<String>,
+}
+
+/// A label used or defined in the function being generated
+struct GotoLabel {
+    name: String,
+    asm_label: String,
+    defined: Option<Span>,
+    /// First goto, for labels that are never defined
+    used: Option<(usize, Span)>,
+}
+
+/// Labels of a switch, collected from its body
+struct Switch {
+    label: usize,
+    /// Promoted type of the controlling expression
+    dtype: Dtype,
+    /// Values converted to dtype, in the order of their labels
+    cases: Vec<(u64, Span)>,
+    default: Option<Span>,
+}
+
+impl Switch {
+    /// Fewest cases worth a jump table
+    const JUMP_TABLE_MIN_CASES: usize = 4;
+    /// Most table slots per case
+    const JUMP_TABLE_MAX_SLOTS_PER_CASE: i128 = 3;
+
+    /// Case value, ordered like the values of dtype
+    fn value(&self, x: u64) -> i128 {
+        if self.dtype.variant.is_unsigned() {
+            x as i128
+        } else {
+            x as i64 as i128
+        }
+    }
+
+    fn value_repr(&self, x: u64) -> String {
+        self.value(x).to_string()
+    }
+
+    fn is_dense(&self) -> bool {
+        let values = self.cases.iter().map(|(x, _)| self.value(*x));
+        let (Some(min), Some(max)) = (values.clone().min(), values.max()) else { return false };
+        self.cases.len() >= Self::JUMP_TABLE_MIN_CASES
+            && max - min < Self::JUMP_TABLE_MAX_SLOTS_PER_CASE * self.cases.len() as i128
+    }
+
+    /// Value in the a register, jumps to the case or fallback
+    fn compare_chain(&self, fallback: &str, scope: &Scope) -> Result<String, Error> {
+        let reg: String = self.dtype.register('a', scope)?;
+        let mut res: String = String::new();
+        for (i, (x, _)) in self.cases.iter().enumerate() {
+            // cmp only takes 32-bit immediates
+            let x: i128 = self.value(*x);
+            if self.dtype.num_bytes(scope)? == 8 && i32::try_from(x).is_err() {
+                res.push_str(format!("\n\tmov rbx, {}\n\tcmp rax, rbx", x as i64).as_str());
+            } else {
+                res.push_str(format!("\n\tcmp {}, {}", reg, x).as_str());
+            }
+            res.push_str(format!("\n\tje .L{}_case{}", self.label, i).as_str());
+        }
+        res.push_str(format!("\n\tjmp {}", fallback).as_str());
+        Ok(res)
+    }
+
+    /// Value in the a register, indexes a table of offsets from the table to each case.
+    /// Slots without a case go to fallback.
+    fn jump_table(&self, fallback: &str, scope: &Scope) -> Result<String, Error> {
+        let min: i128 = self.cases.iter().map(|(x, _)| self.value(*x)).min().unwrap();
+        let max: i128 = self.cases.iter().map(|(x, _)| self.value(*x)).max().unwrap();
+
+        // Index in rax, anything outside the table is above max - min as unsigned
+        let extend: &str = match self.dtype.num_bytes(scope)? {
+            8 => "",
+            _ if self.dtype.variant.is_unsigned() => "\n\tmov eax, eax",
+            _ => "\n\tmovsxd rax, eax",
+        };
+        let mut res: String = format!(
+            "{}\n\tmov rbx, {}\n\tsub rax, rbx\n\tcmp rax, {}\n\tja {}\n\tlea rbx, [rel .L{}_table]\n\tmovsxd rax, DWORD [rbx+rax*4]\n\tadd rax, rbx\n\tjmp rax\n.L{}_table:",
+            extend,
+            min as i64,
+            max - min,
+            fallback,
+            self.label,
+            self.label
+        );
+        for slot in min..=max {
+            let target: String = match self.cases.iter().position(|(x, _)| self.value(*x) == slot) {
+                Some(i) => format!(".L{}_case{}", self.label, i),
+                None => fallback.to_string(),
+            };
+            res.push_str(format!("\n\tdd {} - .L{}_table", target, self.label).as_str());
+        }
+        Ok(res)
+    }
 }
 
 impl Gen {
-    pub fn new() -> Self {
+    pub fn new(entry_stub: bool) -> Self {
         Self {
             scope: Scope::new(),
             data: String::new(),
             label: 0,
+            entry_stub,
+            defined: Vec::new(),
+            declared: Vec::new(),
+            rtype: Dtype::from_fields(DtypeVariant::Void),
+            switches: Vec::new(),
+            jumps: Vec::new(),
+            labels: Vec::new(),
         }
     }
 
@@ -28,16 +152,42 @@ impl Gen {
         #[cfg(target_arch = "x86_64")]
         {
             self.data = String::from("section .rodata\n");
-            let start: String = String::from("global _start\nsection .text\n_start:\n\tcall main\n\tmov rdi, rax\n\tmov rax, 60\n\tsyscall\n");
             let body: String = self.gen_expr(root)?;
 
-            Ok(format!("{}{}\n{}", start, body, self.data))
+            // Defined functions are visible to other units, declared ones come from them
+            let mut symbols: String = String::new();
+            for name in &self.defined {
+                symbols.push_str(format!("global {}\n", name).as_str());
+            }
+            for name in self.declared.iter().filter(|x| !self.defined.contains(x)) {
+                symbols.push_str(format!("extern {}\n", name).as_str());
+            }
+
+            // The kernel leaves argc at rsp, then the argv pointers ending with null, then envp
+            let start: String = if self.entry_stub && self.defines_main() {
+                String::from(
+                    "global _start\nsection .text\n_start:\n\tmov rdi, QWORD [rsp]\n\tlea rsi, [rsp+8]\n\tlea rdx, [rsi+rdi*8+8]\n\tcall main\n\tmov rdi, rax\n\tmov rax, 60\n\tsyscall\n",
+                )
+            } else {
+                String::from("section .text\n")
+            };
+
+            Ok(format!("{}{}{}\n{}", symbols, start, body, self.data))
         }
     }
 
+    /// Functions and structs seen so far
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    pub fn defines_main(&self) -> bool {
+        self.defined.iter().any(|x| x == "main")
+    }
+
     /// Generate instruction(s)
     pub fn gen_expr(&mut self, n: &Node) -> Result<String, Error> {
-        match n.variant.as_ref() {
+        let res: Result<String, Error> = match n.variant.as_ref() {
             NodeVariant::Cpd { .. } => self.gen_cpd(n),
             NodeVariant::Fdef { .. } => self.gen_fdef(n),
             NodeVariant::Return { .. } => self.gen_return(n),
@@ -45,45 +195,78 @@ impl Gen {
             NodeVariant::Var { .. } => self.gen_var(n),
             NodeVariant::Fcall { .. } => self.gen_fcall(n),
             NodeVariant::InitList { .. } => self.gen_init_list(n),
-            NodeVariant::Struct { .. } => {
-                self.scope.push_struct(n)?;
-                Ok(String::new())
-            }
+            NodeVariant::Struct { .. } => self.scope.push_struct(n).map(|_| String::new()),
             NodeVariant::If { .. } => self.gen_if(n),
             NodeVariant::While { .. } => self.gen_while(n),
+            NodeVariant::DoWhile { .. } => self.gen_do_while(n),
+            NodeVariant::For { .. } => self.gen_for(n),
+            NodeVariant::Switch { .. } => self.gen_switch(n),
+            NodeVariant::Case { .. } => self.gen_case(n),
+            NodeVariant::Break | NodeVariant::Continue => self.gen_break(n),
+            NodeVariant::Goto { .. } => self.gen_goto(n),
+            NodeVariant::Label { .. } => self.gen_label(n),
             // NodeVariant::Str { value } => self.gen_str(value.clone()),
             NodeVariant::Noop
             | NodeVariant::Str { .. }
             | NodeVariant::Int { .. }
+            | NodeVariant::Float { .. }
             | NodeVariant::Char { .. } => Ok(String::new()),
             NodeVariant::Binop { .. } => self.gen_binop(n),
             NodeVariant::Unop { .. } => self.gen_unop(n),
-            _ => panic!("[Gen::gen_expr] {:?} not implemented yet", n.variant),
-        }
+            NodeVariant::Postfix { .. } => self.gen_postfix(n),
+            NodeVariant::Ternary { .. } => self.gen_ternary(n),
+            NodeVariant::Cast { .. } => self.gen_cast(n),
+        };
+        res.map_err(|e| e.or_span(n.span))
     }
 
     /// Generate an operand
     pub fn gen_repr(&mut self, n: &Node) -> Result<String, Error> {
-        match n.variant.as_ref() {
-            NodeVariant::Int { value } => Ok(value.to_string()),
+        let res: Result<String, Error> = match n.variant.as_ref() {
+            // Signed values are stored sign-extended
+            NodeVariant::Int { value, dtype } if dtype.is_unsigned() => Ok(value.to_string()),
+            NodeVariant::Int { value, .. } => Ok((*value as i64).to_string()),
             // NodeVariant::Str { value } => self.gen_str(value.clone()),
             NodeVariant::Char { value } => Ok((*value as u8).to_string()),
+            NodeVariant::Float { value, dtype } => Ok(self.gen_float_const(*value, dtype)),
             NodeVariant::Var { name } => {
-                let cv: &CVardef = self.scope.find_vardef(name, n.line)?;
+                let cv: &CVardef = self.scope.find_vardef(name, n.line).map_err(|e| e.or_span(n.span))?;
                 self.gen_stack_repr(&cv.node.dtype(&self.scope)?, cv.stack_offset)
             }
             NodeVariant::Vardef { value, .. } => self.gen_repr(value),
             NodeVariant::Fcall { name, .. } => {
-                util::register('a', &self.scope.find_fdef(name, n.line)?.node, self)
+                util::register('a', &self.scope.find_fdef(name, n.line).map_err(|e| e.or_span(n.span))?.node, self)
             }
             NodeVariant::Binop {
                 btype: TokenType::Dot,
                 ..
             } => util::register('b', n, self),
+            NodeVariant::Binop {
+                btype: TokenType::Comma,
+                r,
+                ..
+            } => self.gen_repr(r),
             NodeVariant::Binop { .. } => util::register('a', n, self),
             NodeVariant::Unop {..} => util::register('a', n, self),
+            NodeVariant::Postfix { .. } => util::register('a', n, self),
+            NodeVariant::Ternary { .. } => util::register('a', n, self),
+            NodeVariant::Cast { .. } => util::register('a', n, self),
             _ => panic!("[Gen::gen_repr] {:?} not implemented yet", n.variant),
-        }
+        };
+        res.map_err(|e| e.or_span(n.span))
+    }
+
+    /// Float literals can't be immediates, so they're read from .rodata
+    fn gen_float_const(&mut self, value: f64, dtype: &DtypeVariant) -> String {
+        let label: String = format!("__float{}", self.label);
+        self.label += 1;
+
+        let (size, bits): (&str, String) = match dtype {
+            DtypeVariant::Float => ("DWORD", format!("dd 0x{:08x}", (value as f32).to_bits())),
+            _ => ("QWORD", format!("dq 0x{:016x}", value.to_bits())),
+        };
+        self.data.push_str(format!("{}: {}\n", label, bits).as_str());
+        format!("{} [rel {}]", size, label)
     }
 
     /// Represent stack at some offset as an operand
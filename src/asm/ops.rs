@@ -1,4 +1,4 @@
-use super::instruction::AsmArg;
+use super::instruction::{float_suffix, mov_instruction, AsmArg};
 use super::util;
 use super::Gen;
 use crate::cdefs::{CVardef, CStruct};
@@ -12,14 +12,29 @@ impl Gen {
         let NodeVariant::Binop { btype, l, r } = n.variant.as_ref() else { unreachable!() };
         match btype {
             TokenType::Dot => self.gen_memb_access(l, r),
-            TokenType::Equal => self.asm_mov(AsmArg::Node(l), AsmArg::Node(r), true),
-            TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Div => {
-                self.asm_arithmetic(AsmArg::Node(l), AsmArg::Node(r), *btype)
-            }
+            TokenType::Equal => self.gen_assign(l, r),
+            TokenType::Plus
+            | TokenType::Minus
+            | TokenType::Star
+            | TokenType::Div
+            | TokenType::Percent
+            | TokenType::Amp
+            | TokenType::Pipe
+            | TokenType::Caret
+            | TokenType::Shl
+            | TokenType::Shr => self.asm_arithmetic(AsmArg::Node(l), AsmArg::Node(r), *btype),
             TokenType::EqualCmp => self.gen_cmp(l, r, "je"),
             TokenType::NotEqual => self.gen_cmp(l, r, "jne"),
+            TokenType::Less
+            | TokenType::Greater
+            | TokenType::LessEqual
+            | TokenType::GreaterEqual => self.gen_relational(l, r, *btype),
             TokenType::And | TokenType::Or => self.gen_andor(l, r, *btype),
-            _ => panic!("[Gen::gen_binop] Binop {:?} not supported.", btype),
+            TokenType::Comma => Ok(format!("{}{}", self.gen_expr(l)?, self.gen_expr(r)?)),
+            _ => match btype.compound_base() {
+                Some(op) => self.gen_compound_assign(n, l, r, op),
+                None => panic!("[Gen::gen_binop] Binop {:?} not supported.", btype),
+            },
         }
     }
 
@@ -29,11 +44,298 @@ impl Gen {
             TokenType::Not => self.gen_not(r),
             TokenType::Amp => self.gen_addressof(r),
             TokenType::Star => self.gen_deref(r),
+            TokenType::Tilde | TokenType::Minus | TokenType::Plus => self.gen_unary_arith(n, r, *utype),
+            TokenType::Inc | TokenType::Dec => {
+                let op: TokenType = if *utype == TokenType::Inc { TokenType::Plus } else { TokenType::Minus };
+                let step: Node = self.increment_step(r)?;
+                self.gen_compound_assign(n, r, &step, op)
+            }
             _ => panic!("[Gen::gen_unop] Unop {:?} not supported.", utype),
         }
     }
 
+    /// Increments the lvalue, then undoes it in the a register for the value before
+    pub fn gen_postfix(&mut self, n: &Node) -> Result<String, Error> {
+        let NodeVariant::Postfix { ptype, l } = n.variant.as_ref() else { unreachable!() };
+        let (op, undo): (TokenType, &str) = match ptype {
+            TokenType::Inc => (TokenType::Plus, "sub"),
+            _ => (TokenType::Minus, "add"),
+        };
+
+        let step: Node = self.increment_step(l)?;
+        let dtype: Dtype = l.dtype(&self.scope)?;
+        let reg: String = dtype.register('a', &self.scope)?;
+        let (undo, undo_step): (String, Node) = if dtype.is_float() {
+            (format!("{}{}", undo, float_suffix(&dtype)), step.cast(&dtype, &self.scope)?)
+        } else {
+            (undo.to_string(), step.clone())
+        };
+        Ok(format!(
+            "\n\t; [postfix]{}\n\t{} {}, {}",
+            self.gen_compound_assign(n, l, &step, op)?,
+            undo,
+            reg,
+            self.gen_repr(&undo_step)?
+        ))
+    }
+
+    /// 1, or the size pointed to for pointers
+    fn increment_step(&self, n: &Node) -> Result<Node, Error> {
+        let mut dtype: Dtype = n.dtype(&self.scope)?;
+        let step: Node = if dtype.nderefs > 0 {
+            dtype.nderefs -= 1;
+            let value: u64 = dtype.num_bytes(&self.scope)? as u64;
+            Node::new(NodeVariant::Int { value, dtype: DtypeVariant::ULong }, n.line)
+        } else {
+            Node::new(NodeVariant::Int { value: 1, dtype: DtypeVariant::Int }, n.line)
+        };
+        Ok(step)
+    }
+
+    /// Scalar results go in the a register, structs leave their address in rax.
+    /// Either arm may push temporaries, so the stack pointer is resynced after each label.
+    pub fn gen_ternary(&mut self, n: &Node) -> Result<String, Error> {
+        let NodeVariant::Ternary { cond, l, r } = n.variant.as_ref() else { unreachable!() };
+        let dtype: Dtype = n.dtype(&self.scope)?;
+
+        let zero_node: Node = Node::new(NodeVariant::Int { value: 0, dtype: DtypeVariant::Int }, n.line);
+        let cmp: String = self.asm_cmp(AsmArg::Node(cond), AsmArg::Node(&zero_node))?;
+
+        let label: usize = self.label;
+        self.label += 1;
+        let l_value: String = self.gen_ternary_arm(l, &dtype)?;
+        let l_sync: String = self.asm_sync_stack();
+        let r_value: String = self.gen_ternary_arm(r, &dtype)?;
+
+        Ok(format!(
+            "\n\t; [ternary]{}\n\tje .L{}_else{}\n\tjmp .L{}_end\n.L{}_else:{}{}\n.L{}_end:{}\n\t; [end ternary]",
+            cmp,
+            label,
+            l_value,
+            label,
+            label,
+            l_sync,
+            r_value,
+            label,
+            self.asm_sync_stack(),
+        ))
+    }
+
+    fn gen_ternary_arm(&mut self, arm: &Node, dtype: &Dtype) -> Result<String, Error> {
+        if matches!(dtype.variant, DtypeVariant::Struct { .. }) && dtype.nderefs == 0 {
+            return match arm.variant.as_ref() {
+                NodeVariant::Ternary { .. } => self.gen_expr(arm),
+                NodeVariant::Var { .. }
+                | NodeVariant::Binop { btype: TokenType::Dot, .. }
+                | NodeVariant::Unop { utype: TokenType::Star, .. } => self.gen_lvalue_addr(arm),
+                // Other arms, like init lists, have no address and go into an unnamed
+                // temporary. Like fcall args it's out of scope after, but keeps its space.
+                _ => {
+                    let var: Node = Node::new(NodeVariant::Var { name: String::new() }, arm.line);
+                    let temp: Node = Node::new(
+                        NodeVariant::Vardef {
+                            var,
+                            value: arm.clone(),
+                            dtype: dtype.clone(),
+                        },
+                        arm.line,
+                    );
+                    let value: String = self.gen_vardef(&temp)?;
+                    self.scope.pop_vardef();
+                    Ok(format!("{}\n\tlea rax, [rbp{:+}]", value, self.scope.stack_offset()))
+                }
+            };
+        }
+
+        let arm: Node = self.convert_for(arm, dtype)?;
+        let reg: String = dtype.register('a', &self.scope)?;
+        self.asm_mov(AsmArg::Register(reg.as_str()), AsmArg::Node(&arm), true)
+    }
+
+    /// Store r in the lvalue l, leaving the stored value in the a register
+    fn gen_assign(&mut self, l: &Node, r: &Node) -> Result<String, Error> {
+        let dtype: Dtype = l.dtype(&self.scope)?;
+        let r: Node = self.convert_for(r, &dtype)?;
+        if matches!(l.variant.as_ref(), NodeVariant::Var { .. }) {
+            let assign: String = self.asm_mov(AsmArg::Node(&l.clone()), AsmArg::Node(&r), true)?;
+            if !dtype.is_arithmetic() && dtype.nderefs == 0 {
+                return Ok(assign);
+            }
+
+            // The value of the assignment, for a = b = c
+            let reg_a: String = dtype.register('a', &self.scope)?;
+            let value: String = self.asm_mov(AsmArg::Register(&reg_a), AsmArg::Node(l), false)?;
+            return Ok(format!("{}{}", assign, value));
+        }
+
+        // The value goes on the stack first, so evaluating it can't clobber the address
+        let value_expr: String = self.gen_expr(&r)?;
+        self.scope.stack_offset_change_n(&r, -1)?;
+        let value_offset: i32 = self.scope.stack_offset();
+        let value_to_stack: String = self.gen_stack_push(&r)?;
+
+        let reg_a: String = dtype.register('a', &self.scope)?;
+        let reg_b: String = dtype.register('b', &self.scope)?;
+        let value: String = self.gen_stack_repr(&dtype, value_offset)?;
+        let target: String = format!("{} [rax]", dtype.deref(&self.scope)?);
+        Ok(format!(
+            "\n\t; [assign] value{}{}\n\t; [assign] address{}\n\t{} {}, {}\n\t{} {}, {}\n\t{} {}, {}",
+            value_expr,
+            value_to_stack,
+            self.gen_lvalue_addr(l)?,
+            mov_instruction(&reg_b, &value),
+            reg_b,
+            value,
+            mov_instruction(&target, &reg_b),
+            target,
+            reg_b,
+            mov_instruction(&reg_a, &reg_b),
+            reg_a,
+            reg_b,
+        ))
+    }
+
+    /// l op= r, computing the address of l once.
+    /// The address is kept in a hidden pointer variable, and the assignment becomes *p = *p op r.
+    fn gen_compound_assign(&mut self, n: &Node, l: &Node, r: &Node, op: TokenType) -> Result<String, Error> {
+        let addr: String = self.gen_lvalue_addr(l)?;
+
+        let mut dtype: Dtype = l.dtype(&self.scope)?;
+        dtype.nderefs += 1;
+        let var: Node = Node::new(NodeVariant::Var { name: format!(".lvalue{}", self.label) }, n.line);
+        self.label += 1;
+        let vardef: Node = Node::new(
+            NodeVariant::Vardef {
+                var: var.clone(),
+                value: Node::new(NodeVariant::Noop, n.line),
+                dtype,
+            },
+            n.line,
+        );
+        self.scope.stack_offset_change_n(&vardef, -1)?;
+        self.scope.push_vardef(&vardef);
+        let addr_to_stack: String = format!(
+            "{}\n\tmov QWORD [rbp{:+}], rax",
+            self.asm_extend_stack(8),
+            self.scope.stack_offset()
+        );
+
+        let target: Node = Node::new(NodeVariant::Unop { utype: TokenType::Star, r: var }, n.line).with_span(l.span);
+        let value: Node = Node::new(
+            NodeVariant::Binop {
+                btype: op,
+                l: target.clone(),
+                r: r.clone(),
+            },
+            n.line,
+        )
+        .with_span(n.span);
+        let assign: String = self.gen_assign(&target, &value)?;
+        self.scope.pop_vardef();
+
+        Ok(format!("\n\t; [compound assign]{}{}{}", addr, addr_to_stack, assign))
+    }
+
+    /// Address of an lvalue in rax
+    pub fn gen_lvalue_addr(&mut self, n: &Node) -> Result<String, Error> {
+        match n.variant.as_ref() {
+            NodeVariant::Var { .. } => self.gen_addressof(n),
+            NodeVariant::Binop { btype: TokenType::Dot, l, r } => {
+                let (offset, _): (i32, Dtype) = self.member_stack_offset(l, r)?;
+                Ok(format!("\n\tlea rax, [rbp{:+}]", offset))
+            }
+            NodeVariant::Unop { utype: TokenType::Star, r } => {
+                self.asm_mov(AsmArg::Register("rax"), AsmArg::Node(r), true)
+            }
+            _ => Err(Error::new(ErrorType::InvalidLvalue, n.line).or_span(n.span)),
+        }
+    }
+
+    /// Result in the a register of the cast's type
+    pub fn gen_cast(&mut self, n: &Node) -> Result<String, Error> {
+        let NodeVariant::Cast { dtype, value } = n.variant.as_ref() else { unreachable!() };
+        let src: Dtype = value.dtype(&self.scope)?;
+        let src_reg: String = src.register('a', &self.scope)?;
+        let dest_reg: String = dtype.register('a', &self.scope)?;
+        let value_to_reg: String = self.asm_mov(AsmArg::Register(src_reg.as_str()), AsmArg::Node(value), true)?;
+
+        // Narrowing just uses the lower part of the register
+        let nbytes: i32 = src.num_bytes(&self.scope)?;
+        let extend: String = if src.is_float() || dtype.is_float() {
+            self.asm_float_convert(&src, dtype)?
+        } else if dtype.num_bytes(&self.scope)? <= nbytes {
+            String::new()
+        } else {
+            match (nbytes, src.variant.is_unsigned()) {
+                // Writing a 32-bit register clears the upper half
+                (4, true) => format!("\n\tmov {}, {}", src_reg, src_reg),
+                (4, false) => format!("\n\tmovsxd {}, {}", dest_reg, src_reg),
+                (_, true) => format!("\n\tmovzx {}, {}", dest_reg, src_reg),
+                (_, false) => format!("\n\tmovsx {}, {}", dest_reg, src_reg),
+            }
+        };
+
+        Ok(format!("\n\t; [cast]{}{}\n\t; [end cast]", value_to_reg, extend))
+    }
+
+    /// Conversion of the a register between float and other arithmetic types.
+    /// 64-bit unsigned values are converted as if they were signed.
+    fn asm_float_convert(&self, src: &Dtype, dest: &Dtype) -> Result<String, Error> {
+        if src.is_float() && dest.is_float() {
+            return Ok(format!("\n\tcvt{}2{} xmm0, xmm0", float_suffix(src), float_suffix(dest)));
+        }
+
+        // cvt instructions take 32 or 64-bit integer registers, unsigned int needs 64 bits to fit
+        let integer: &Dtype = if src.is_float() { dest } else { src };
+        let wide: bool = integer.num_bytes(&self.scope)? == 8 || integer.variant.is_unsigned();
+        let reg: &str = if wide { "rax" } else { "eax" };
+        Ok(if src.is_float() {
+            format!("\n\tcvtt{}2si {}, xmm0", float_suffix(src), reg)
+        } else {
+            let extend: &str = match (src.num_bytes(&self.scope)?, wide) {
+                (1, false) => "\n\tmovsx eax, al",
+                (1, true) => "\n\tmovzx eax, al",
+                // Writing a 32-bit register clears the upper half
+                (4, true) => "\n\tmov eax, eax",
+                _ => "",
+            };
+            format!("{}\n\tcvtsi2{} xmm0, {}", extend, float_suffix(dest), reg)
+        })
+    }
+
+    /// n converted to dtype if both are arithmetic types, for assignment
+    pub fn convert_for(&self, n: &Node, dtype: &Dtype) -> Result<Node, Error> {
+        if n.dtype(&self.scope)?.is_arithmetic() && dtype.is_arithmetic() {
+            n.cast(dtype, &self.scope)
+        } else {
+            Ok(n.clone())
+        }
+    }
+
+    /// Operands of an arithmetic operator converted to their common type
+    pub fn convert_operands(&self, l: &Node, r: &Node) -> Result<(Node, Node), Error> {
+        let (ldtype, rdtype): (Dtype, Dtype) = (l.dtype(&self.scope)?, r.dtype(&self.scope)?);
+        if !ldtype.is_arithmetic() || !rdtype.is_arithmetic() {
+            return Ok((l.clone(), r.clone()));
+        }
+
+        let common: Dtype = Dtype::common(&ldtype, &rdtype);
+        Ok((l.cast(&common, &self.scope)?, r.cast(&common, &self.scope)?))
+    }
+
     fn gen_memb_access(&mut self, l: &Node, r: &Node) -> Result<String, Error> {
+        // mov register, member
+        let (offset, memb_dtype): (i32, Dtype) = self.member_stack_offset(l, r)?;
+        let reg: String = memb_dtype.register('b', &self.scope)?;
+        self.asm_mov(
+            AsmArg::Register(&reg),
+            AsmArg::Stack(&memb_dtype, offset),
+            true,
+        )
+    }
+
+    /// Stack offset and type of member r of the struct l
+    fn member_stack_offset(&self, l: &Node, r: &Node) -> Result<(i32, Dtype), Error> {
         // Member access must be an identifier
         if !matches!(r.variant.as_ref(), NodeVariant::Var { .. }) {
             return Err(Error::new(ErrorType::StructMemberVarNonId(r), r.line));
@@ -80,15 +382,7 @@ impl Gen {
 
         let rel_offset: i32 = sdef.memb_stack_offsets[index];
         let memb_dtype: Dtype = fields[index].dtype(&self.scope)?;
-
-        // mov register, member
-        let offset: i32 = l_offset + rel_offset;
-        let reg: String = memb_dtype.register('b', &self.scope)?;
-        self.asm_mov(
-            AsmArg::Register(&reg),
-            AsmArg::Stack(&memb_dtype, offset),
-            true,
-        )
+        Ok((l_offset + rel_offset, memb_dtype))
     }
 
     fn gen_cmp(&mut self, l: &Node, r: &Node, jmp: &str) -> Result<String, Error> {
@@ -96,18 +390,46 @@ impl Gen {
             cmp l, r
             <zf conditional>
         */
+        let (l, r): (Node, Node) = self.convert_operands(l, r)?;
         Ok(format!(
             "{}{}",
-            self.asm_cmp(AsmArg::Node(l), AsmArg::Node(r))?,
-            self.asm_zf_conditional(util::register('a', l, self)?.as_str(), jmp)
+            self.asm_cmp(AsmArg::Node(&l), AsmArg::Node(&r))?,
+            self.asm_zf_conditional("eax", jmp)
         ))
     }
 
+    /// Unsigned and pointer operands use the below/above jumps
+    fn gen_relational(&mut self, l: &Node, r: &Node, op: TokenType) -> Result<String, Error> {
+        let (l, r): (Node, Node) = self.convert_operands(l, r)?;
+        let dtype: Dtype = l.dtype(&self.scope)?;
+        // Floats only use ja and jae, which are false for NaN, so l < r is r > l
+        let (l, r, op): (Node, Node, TokenType) = match op {
+            TokenType::Less if dtype.is_float() => (r, l, TokenType::Greater),
+            TokenType::LessEqual if dtype.is_float() => (r, l, TokenType::GreaterEqual),
+            _ => (l, r, op),
+        };
+        // Float compares set the flags like unsigned ones
+        let unsigned: bool = dtype.nderefs > 0 || dtype.variant.is_unsigned() || dtype.is_float();
+        let jmp: &str = match (op, unsigned) {
+            (TokenType::Less, false) => "jl",
+            (TokenType::Greater, false) => "jg",
+            (TokenType::LessEqual, false) => "jle",
+            (TokenType::GreaterEqual, false) => "jge",
+            (TokenType::Less, true) => "jb",
+            (TokenType::Greater, true) => "ja",
+            (TokenType::LessEqual, true) => "jbe",
+            (TokenType::GreaterEqual, true) => "jae",
+            _ => unreachable!(),
+        };
+        self.gen_cmp(&l, &r, jmp)
+    }
+
     fn gen_andor(&mut self, l: &Node, r: &Node, op: TokenType) -> Result<String, Error> {
-        let ar: String = util::register('a', l, self)?;
-        let br: String = util::register('b', l, self)?;
+        // Comparison results are ints
+        let ar: String = String::from("eax");
+        let br: String = String::from("ebx");
 
-        let zero_node: Node = Node::new(NodeVariant::Int { value: 0 }, l.line);
+        let zero_node: Node = Node::new(NodeVariant::Int { value: 0, dtype: DtypeVariant::Int }, l.line);
         let lcmp: String = format!(
             "{}{}",
             self.gen_cmp(l, &zero_node, "jne")?,
@@ -138,10 +460,33 @@ impl Gen {
     }
 
     fn gen_not(&mut self, n: &Node) -> Result<String, Error> {
-        let zero_node: Node = Node::new(NodeVariant::Int { value: 0 }, n.line);
+        let zero_node: Node = Node::new(NodeVariant::Int { value: 0, dtype: DtypeVariant::Int }, n.line);
         Ok(format!("\n\t; [not]{}", self.gen_cmp(n, &zero_node, "je")?))
     }
 
+    /// ~, - and +, result in the a register of the promoted type
+    fn gen_unary_arith(&mut self, n: &Node, r: &Node, utype: TokenType) -> Result<String, Error> {
+        let dtype: Dtype = n.dtype(&self.scope)?;
+        let r: Node = r.cast(&dtype, &self.scope)?;
+        let reg: String = util::register('a', n, self)?;
+        let op: String = match utype {
+            TokenType::Tilde => format!("\n\tnot {}", reg),
+            // Flip the sign bit, 0 - x would give 0 for -0.0
+            TokenType::Minus if dtype.variant == DtypeVariant::Float => {
+                format!("\n\tmovd eax, {}\n\txor eax, 0x80000000\n\tmovd {}, eax", reg, reg)
+            }
+            TokenType::Minus if dtype.is_float() => format!("\n\tmovq rax, {}\n\tbtc rax, 63\n\tmovq {}, rax", reg, reg),
+            TokenType::Minus => format!("\n\tneg {}", reg),
+            _ => String::new(),
+        };
+        Ok(format!(
+            "\n\t; [unary {:?}]{}{}",
+            utype,
+            self.asm_mov(AsmArg::Register(reg.as_str()), AsmArg::Node(&r), true)?,
+            op
+        ))
+    }
+
     fn gen_addressof(&mut self, n: &Node) -> Result<String, Error> {
         if !matches!(n.variant.as_ref(), NodeVariant::Var {..}) {
             return Err(Error::new(ErrorType::InvalidAddressof(n.variant.as_ref()), n.line));
@@ -151,17 +496,14 @@ impl Gen {
         let offset: i32 = vardef.stack_offset;
 
         // lea rax, [rbp-(n offset)]
-        Ok(format!("\n\t; [addressof]\n\tlea rax, [rbp{}]", offset))
+        Ok(format!("\n\t; [addressof]\n\tlea rax, [rbp{:+}]", offset))
     }
 
     fn gen_deref(&mut self, n: &Node) -> Result<String, Error> {
         /*
-           ; Load n address into rax
-           mov rax, [rbp-(n offset)]
-           ; Dereference rax and store in rax (may be repeated for nested statements)
-           mov rax, QWORD [rax]
-           ; Store result in appropriately-sized register
-           ; In this case if rax was an int* then eax would be used, and DWORD would be used for dereferencing
+           ; Load the pointer n into rax
+           mov rax, QWORD [rbp-(n offset)]
+           ; Dereference rax into the register sized for the pointed to type
            mov eax, DWORD [rax]
         */
 
@@ -169,22 +511,16 @@ impl Gen {
             return Err(Error::new(ErrorType::InvalidDeref(n.variant.as_ref()), n.line));
         }
 
-        let vardef: &CVardef = self.scope.find_vardef(n.var_name().as_str(), n.line)?;
-        let offset: i32 = vardef.stack_offset;
-        let stack_to_rax: String = format!("\n\tmov rax, {}", self.gen_stack_repr(&n.dtype(&self.scope)?, offset)?);
-
-        let dtype: Dtype = n.dtype(&self.scope)?;
-        // One deref left when moving to appropriately sized register
-        // However the value passed is already the value being dereferenced
-        // so these two effects cancel out, leaving dtype.nderefs
-        let derefs: String = "\n\tmov rax, QWORD [rax]".repeat(dtype.nderefs);
-
-        let rax_to_result: String = format!("\n\tmov {}, {} [rax]", dtype.register('a', &self.scope)?, dtype.deref(&self.scope)?);
-
+        let mut dtype: Dtype = n.dtype(&self.scope)?;
+        dtype.nderefs -= 1;
+        let reg: String = dtype.register('a', &self.scope)?;
+        let src: String = format!("{} [rax]", dtype.deref(&self.scope)?);
         Ok(format!(
-            "\n\t; [deref stack to rax]{}\n\t; [deref handle nested]{}
-            \n\t; [deref rax to result]{}",
-            stack_to_rax, derefs, rax_to_result
+            "\n\t; [deref]{}\n\t{} {}, {}",
+            self.asm_mov(AsmArg::Register("rax"), AsmArg::Node(n), true)?,
+            mov_instruction(&reg, &src),
+            reg,
+            src
         ))
     }
 }
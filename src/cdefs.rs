@@ -1,5 +1,5 @@
 use crate::error::{Error, ErrorType};
-use crate::node::{Node, NodeVariant};
+use crate::node::{Dtype, Node, NodeVariant};
 use crate::scope::Scope;
 
 #[derive(Clone)]
@@ -18,27 +18,71 @@ impl CVardef {
     }
 }
 
+/// Integer argument registers in System V order, as 64, 32 and 8-bit names
+const INT_ARG_REGISTERS: [[&str; 3]; 6] = [
+    ["rdi", "edi", "dil"],
+    ["rsi", "esi", "sil"],
+    ["rdx", "edx", "dl"],
+    ["rcx", "ecx", "cl"],
+    ["r8", "r8d", "r8b"],
+    ["r9", "r9d", "r9b"],
+];
+
+/// xmm0 to xmm7 pass float arguments
+const FLOAT_ARG_REGISTERS: usize = 8;
+
 #[derive(Clone)]
 pub struct CFdef {
     pub node: Node,
-    /// Abs value offsets
+    /// Abs value offsets, 0 for params passed in registers
     pub param_stack_offsets: Vec<i32>,
+    /// Register each param is passed in, sized to the param
+    pub param_registers: Vec<Option<String>>,
 }
 
 impl CFdef {
     pub fn new(node: &Node, scope: &Scope) -> Result<Self, Error> {
         let mut stack_offsets: Vec<i32> = Vec::new();
+        let mut registers: Vec<Option<String>> = Vec::new();
         let NodeVariant::Fdef { params, .. } = node.variant.as_ref() else { unreachable!() };
 
+        // Like System V, integers and pointers go in the next free integer register and floats
+        // in the next xmm register. Structs and params left without a register go on the
+        // stack in 8 byte slots, the first one lowest.
         let mut offset: i32 = 16;
+        let mut n_ints: usize = 0;
+        let mut n_floats: usize = 0;
         for param in params.iter() {
-            stack_offsets.push(offset);
-            offset += param.dtype(scope)?.num_bytes(scope)?;
+            let dtype: Dtype = param.dtype(scope)?;
+            let nbytes: i32 = dtype.num_bytes(scope)?;
+            let register: Option<String> = if dtype.is_float() && n_floats < FLOAT_ARG_REGISTERS {
+                n_floats += 1;
+                Some(format!("xmm{}", n_floats - 1))
+            } else if (dtype.is_integer() || dtype.nderefs > 0) && n_ints < INT_ARG_REGISTERS.len() {
+                n_ints += 1;
+                let size: usize = match nbytes {
+                    8 => 0,
+                    4 => 1,
+                    _ => 2,
+                };
+                Some(INT_ARG_REGISTERS[n_ints - 1][size].to_string())
+            } else {
+                None
+            };
+
+            if register.is_some() {
+                stack_offsets.push(0);
+            } else {
+                stack_offsets.push(offset);
+                offset += (nbytes + 7) / 8 * 8;
+            }
+            registers.push(register);
         }
 
         Ok(Self {
             node: node.clone(),
             param_stack_offsets: stack_offsets,
+            param_registers: registers,
         })
     }
 }
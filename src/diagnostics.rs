@@ -0,0 +1,140 @@
+use crate::error::Error;
+use crate::json::Json;
+use crate::span::Span;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DiagnosticsFormat {
+    /// Colored, with source context
+    Text,
+    /// Array of diagnostic objects
+    Json,
+    /// SARIF 2.1.0 log
+    Sarif,
+}
+
+impl DiagnosticsFormat {
+    pub fn new(format: &str) -> Result<Self, String> {
+        match format {
+            "text" => Ok(DiagnosticsFormat::Text),
+            "json" => Ok(DiagnosticsFormat::Json),
+            "sarif" => Ok(DiagnosticsFormat::Sarif),
+            _ => Err(format!(
+                "Unknown diagnostics format '{}', expected 'text', 'json' or 'sarif'.",
+                format
+            )),
+        }
+    }
+}
+
+/// Location fields, null for errors not tied to a source line
+fn location_json(file: Option<&str>, line: usize, span: Option<Span>) -> Vec<(&'static str, Json)> {
+    let int = |x: Option<usize>| x.filter(|_| line != 0).map_or(Json::Null, |x| Json::Int(x as i64));
+    vec![
+        ("file", file.map_or(Json::Null, Json::str)),
+        ("line", int(Some(line))),
+        ("column", int(span.map(|x| x.column))),
+        ("end_line", int(span.map(|x| x.end_line))),
+        ("end_column", int(span.map(|x| x.end_column))),
+    ]
+}
+
+fn error_json(e: &Error) -> Json {
+    let notes: Vec<Json> = e
+        .notes()
+        .iter()
+        .map(|note| {
+            let mut fields: Vec<(&str, Json)> = vec![
+                ("severity", Json::str("note")),
+                ("message", Json::str(note.message.as_str())),
+            ];
+            fields.extend(location_json(note.file.as_deref().or(e.file()), note.line, note.span));
+            Json::object(fields)
+        })
+        .collect();
+
+    let mut fields: Vec<(&str, Json)> = vec![
+        ("severity", Json::str("error")),
+        ("kind", Json::str(e.kind())),
+        ("message", Json::str(e.message())),
+    ];
+    fields.extend(location_json(e.file(), e.line(), e.span()));
+    fields.push(("notes", Json::Array(notes)));
+    Json::object(fields)
+}
+
+/// Physical location of a SARIF result, None for errors not tied to a source line
+fn sarif_location(file: Option<&str>, line: usize, span: Option<Span>) -> Option<Json> {
+    if line == 0 {
+        return None;
+    }
+
+    let mut region: Vec<(&str, Json)> = vec![("startLine", Json::Int(line as i64))];
+    if let Some(span) = span {
+        region.push(("startColumn", Json::Int(span.column as i64)));
+        region.push(("endLine", Json::Int(span.end_line as i64)));
+        region.push(("endColumn", Json::Int(span.end_column as i64)));
+    }
+
+    let mut location: Vec<(&str, Json)> = Vec::new();
+    if let Some(file) = file {
+        location.push(("artifactLocation", Json::object(vec![("uri", Json::str(file))])));
+    }
+    location.push(("region", Json::object(region)));
+    Some(Json::object(vec![("physicalLocation", Json::object(location))]))
+}
+
+fn sarif_result(e: &Error) -> Json {
+    let message = |text: &str| Json::object(vec![("text", Json::str(text))]);
+
+    let mut related: Vec<Json> = Vec::new();
+    for (i, note) in e.notes().iter().enumerate() {
+        let Some(Json::Object(mut location)) = sarif_location(note.file.as_deref().or(e.file()), note.line, note.span) else {
+            continue;
+        };
+        location.insert(0, (String::from("id"), Json::Int(i as i64)));
+        location.push((String::from("message"), message(note.message.as_str())));
+        related.push(Json::Object(location));
+    }
+
+    Json::object(vec![
+        ("ruleId", Json::str(e.kind())),
+        ("level", Json::str("error")),
+        ("message", message(e.message())),
+        (
+            "locations",
+            Json::Array(sarif_location(e.file(), e.line(), e.span()).into_iter().collect()),
+        ),
+        ("relatedLocations", Json::Array(related)),
+    ])
+}
+
+/// Errors in a machine-readable format, on one line.
+/// Text diagnostics are printed with their program instead.
+pub fn render(errors: &[Error], format: DiagnosticsFormat) -> String {
+    let json: Json = match format {
+        DiagnosticsFormat::Text => return String::new(),
+        DiagnosticsFormat::Json => Json::Array(errors.iter().map(error_json).collect()),
+        DiagnosticsFormat::Sarif => Json::object(vec![
+            ("$schema", Json::str("https://json.schemastore.org/sarif-2.1.0.json")),
+            ("version", Json::str("2.1.0")),
+            (
+                "runs",
+                Json::Array(vec![Json::object(vec![
+                    (
+                        "tool",
+                        Json::object(vec![(
+                            "driver",
+                            Json::object(vec![
+                                ("name", Json::str("cc")),
+                                ("version", Json::str(env!("CARGO_PKG_VERSION"))),
+                            ]),
+                        )]),
+                    ),
+                    ("results", Json::Array(errors.iter().map(sarif_result).collect())),
+                ])]),
+            ),
+        ]),
+    };
+
+    format!("{}\n", json)
+}
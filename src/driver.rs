@@ -0,0 +1,556 @@
+use crate::asm::Gen;
+use crate::diagnostics::{self, DiagnosticsFormat};
+use crate::dump::{self, DumpFormat};
+use crate::error::{Error, ErrorType};
+use crate::node::Node;
+use crate::preprocess;
+use crate::run::{self, RunStatus};
+use crate::session::Session;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How far the driver takes each input before stopping.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Stage {
+    /// -E: stop after preprocessing
+    Preprocess,
+    /// -fsyntax-only: preprocess, parse and type check
+    SyntaxOnly,
+    /// -S: stop after assembly generation
+    Assembly,
+    /// -c: stop after assembling into an object file
+    Object,
+    /// Default: link into an executable
+    Executable,
+}
+
+/// Kind of input file, determined by its extension
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum InputKind {
+    /// .c, preprocessed .i, or stdin
+    C,
+    /// .s, assembled with nasm
+    Asm,
+    /// Anything else is passed to the linker
+    Object,
+}
+
+/// Command-line macro, applied in order
+#[derive(Debug, Clone)]
+pub enum MacroArg {
+    /// -DNAME[=value], value defaults to 1
+    Define(String, String),
+    /// -UNAME
+    Undefine(String),
+}
+
+pub struct Options {
+    pub inputs: Vec<String>,
+    /// None if no -o was given, "-" for stdout
+    pub output: Option<String>,
+    pub stage: Stage,
+    /// -nostartfiles: never emit _start, an explicit crt object provides it
+    pub nostartfiles: bool,
+    /// -save-temps: keep intermediate files in the working directory
+    pub save_temps: bool,
+    pub macros: Vec<MacroArg>,
+    /// -I
+    pub include_dirs: Vec<String>,
+    /// -isystem
+    pub system_include_dirs: Vec<String>,
+    /// -include
+    pub forced_includes: Vec<String>,
+    /// --dump-tokens[=tree|json]
+    pub dump_tokens: Option<DumpFormat>,
+    /// --dump-ast[=tree|json]
+    pub dump_ast: Option<DumpFormat>,
+    /// --dump-scope[=tree|json]
+    pub dump_scope: Option<DumpFormat>,
+    /// -fdiagnostics-format=text|json|sarif
+    pub diagnostics_format: DiagnosticsFormat,
+    /// cc run: execute the linked program
+    pub run: bool,
+    /// Arguments after -- are passed to the program
+    pub run_args: Vec<String>,
+    /// --timeout=<secs>: kill the program if it runs longer
+    pub timeout: Option<Duration>,
+}
+
+impl InputKind {
+    pub fn new(path: &str) -> Self {
+        if path == "-" {
+            return InputKind::C;
+        }
+
+        match Path::new(path).extension().and_then(|x| x.to_str()) {
+            Some("c") | Some("i") => InputKind::C,
+            Some("s") => InputKind::Asm,
+            _ => InputKind::Object,
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            inputs: Vec::new(),
+            output: None,
+            stage: Stage::Executable,
+            nostartfiles: false,
+            save_temps: false,
+            macros: Vec::new(),
+            include_dirs: Vec::new(),
+            system_include_dirs: Vec::new(),
+            forced_includes: Vec::new(),
+            dump_tokens: None,
+            dump_ast: None,
+            dump_scope: None,
+            diagnostics_format: DiagnosticsFormat::Text,
+            run: false,
+            run_args: Vec::new(),
+            timeout: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options: Options = Options::default();
+
+        let mut args: &[String] = args;
+        if args.first().is_some_and(|x| x == "run") {
+            options.run = true;
+            args = &args[1..];
+            if let Some(i) = args.iter().position(|x| x == "--") {
+                options.run_args = args[i + 1..].to_vec();
+                args = &args[..i];
+            }
+        }
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            // Value of an option that can be attached (-Ifoo) or separate (-I foo)
+            let mut value = |flag: &str| -> Result<String, String> {
+                if arg.len() > flag.len() {
+                    Ok(arg[flag.len()..].to_string())
+                } else {
+                    iter.next()
+                        .cloned()
+                        .ok_or(format!("Missing argument after '{}'.", flag))
+                }
+            };
+
+            match arg.as_str() {
+                "-o" => options.output = Some(value("-o")?),
+                "-isystem" => options.system_include_dirs.push(value("-isystem")?),
+                "-include" => options.forced_includes.push(value("-include")?),
+                "-E" => options.stage = Stage::Preprocess,
+                "-S" => options.stage = Stage::Assembly,
+                "-c" => options.stage = Stage::Object,
+                "-fsyntax-only" => options.stage = Stage::SyntaxOnly,
+                "-nostartfiles" => options.nostartfiles = true,
+                "-save-temps" => options.save_temps = true,
+                "-" => options.inputs.push(arg.clone()),
+                _ if arg.starts_with("--timeout=") && options.run => {
+                    let secs: &str = &arg["--timeout=".len()..];
+                    options.timeout = Some(
+                        secs.parse::<f64>()
+                            .ok()
+                            .and_then(|x| Duration::try_from_secs_f64(x).ok())
+                            .ok_or(format!("Invalid timeout '{}'.", secs))?,
+                    );
+                }
+                _ if arg.starts_with("-fdiagnostics-format=") => {
+                    options.diagnostics_format =
+                        DiagnosticsFormat::new(&arg["-fdiagnostics-format=".len()..])?;
+                }
+                _ if arg.starts_with("--dump-") => {
+                    let (flag, format) = arg.split_once('=').unwrap_or((arg, "tree"));
+                    let format: Option<DumpFormat> = Some(DumpFormat::new(format)?);
+                    match flag {
+                        "--dump-tokens" => options.dump_tokens = format,
+                        "--dump-ast" => options.dump_ast = format,
+                        "--dump-scope" => options.dump_scope = format,
+                        _ => return Err(format!("Unrecognized command-line option '{}'.", arg)),
+                    }
+                }
+                _ if arg.starts_with("-D") => {
+                    let def: String = value("-D")?;
+                    options.macros.push(match def.split_once('=') {
+                        Some((name, expr)) => MacroArg::Define(name.to_string(), expr.to_string()),
+                        None => MacroArg::Define(def, String::from("1")),
+                    });
+                }
+                _ if arg.starts_with("-U") => options.macros.push(MacroArg::Undefine(value("-U")?)),
+                _ if arg.starts_with("-I") => options.include_dirs.push(value("-I")?),
+                _ if arg.starts_with("-o") => options.output = Some(value("-o")?),
+                _ if arg.starts_with('-') => {
+                    return Err(format!("Unrecognized command-line option '{}'.", arg))
+                }
+                _ => options.inputs.push(arg.clone()),
+            }
+        }
+
+        if options.inputs.is_empty() {
+            return Err(String::from("No input files provided."));
+        }
+
+        // Every C file gets its own output unless everything is linked together
+        let ncompiled: usize = options
+            .inputs
+            .iter()
+            .filter(|x| InputKind::new(x) != InputKind::Object)
+            .count();
+        if options.output.is_some()
+            && ncompiled > 1
+            && matches!(options.stage, Stage::Assembly | Stage::Object)
+        {
+            return Err(String::from(
+                "Cannot specify '-o' with '-S' or '-c' with multiple files.",
+            ));
+        }
+        if options.output.is_some() && options.inputs.len() > 1 && options.stage == Stage::Preprocess {
+            return Err(String::from("Cannot specify '-o' with '-E' with multiple files."));
+        }
+        if options.run {
+            if options.stage != Stage::Executable || options.dumps() {
+                return Err(String::from("'run' only supports building an executable."));
+            }
+            // Stdin belongs to the program
+            if options.inputs.iter().any(|x| x == "-") {
+                return Err(String::from("Cannot read input from stdin with 'run'."));
+            }
+            if options.output.as_deref() == Some("-") {
+                return Err(String::from("Cannot specify '-o -' with 'run'."));
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Dumps replace the normal output
+    pub fn dumps(&self) -> bool {
+        self.dump_tokens.is_some() || self.dump_ast.is_some() || self.dump_scope.is_some()
+    }
+
+    /// Output path used when no -o was given.
+    /// Intermediate outputs are named after the input, executables default to a.out.
+    pub fn default_output(&self, input: &str) -> String {
+        let stem: &str = if input == "-" {
+            "a"
+        } else {
+            Path::new(input)
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .unwrap_or("a")
+        };
+
+        match self.stage {
+            Stage::Assembly => format!("{}.s", stem),
+            Stage::Object => format!("{}.o", stem),
+            // Preprocessed output goes to stdout unless -o is given
+            Stage::Preprocess => String::from("-"),
+            Stage::SyntaxOnly | Stage::Executable => String::from("a.out"),
+        }
+    }
+}
+
+/// Directory holding intermediate files.
+/// Private and removed on drop, unless -save-temps keeps them in the working directory.
+pub struct TempDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempDir {
+    pub fn new(keep: bool) -> Result<Self, String> {
+        if keep {
+            return Ok(Self {
+                path: PathBuf::from("."),
+                keep,
+            });
+        }
+
+        let base: PathBuf = std::env::temp_dir();
+        for attempt in 0..100 {
+            let path: PathBuf = base.join(format!("cc-{}-{}", std::process::id(), attempt));
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(Self { path, keep }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(format!(
+                        "Unable to create temporary directory '{}': {}.",
+                        path.display(),
+                        e
+                    ))
+                }
+            }
+        }
+
+        Err(String::from("Unable to create temporary directory."))
+    }
+
+    /// Path of an intermediate file for the index-th input.
+    /// Saved temps are named after the input like gcc does, private ones are prefixed
+    /// with the index so inputs with the same stem don't collide.
+    pub fn file(&self, index: usize, input: &str, ext: &str) -> String {
+        let stem: &str = Path::new(input)
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .filter(|_| input != "-")
+            .unwrap_or("a");
+
+        let name: String = if self.keep {
+            format!("{}.{}", stem, ext)
+        } else {
+            format!("{}-{}.{}", index, stem, ext)
+        };
+
+        self.path.join(name).to_string_lossy().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if !self.keep {
+            fs::remove_dir_all(&self.path).ok();
+        }
+    }
+}
+
+/// "-" reads from stdin
+fn read_input(path: &str) -> Result<String, Error> {
+    let mut prog: String = String::new();
+    let res = if path == "-" {
+        std::io::stdin().read_to_string(&mut prog).map(|_| ())
+    } else {
+        fs::read_to_string(path).map(|x| prog = x)
+    };
+
+    res.map(|_| prog)
+        .map_err(|e| Error::new(ErrorType::ReadFile(display_name(path), e.to_string()), 0))
+}
+
+/// "-" writes to stdout
+fn write_output(path: &str, contents: &[u8]) -> Result<(), Error> {
+    let res = if path == "-" {
+        std::io::stdout().write_all(contents)
+    } else {
+        fs::File::create(path).and_then(|mut f| f.write_all(contents))
+    };
+
+    res.map_err(|e| Error::new(ErrorType::WriteFile(path, e.to_string()), 0))
+}
+
+/// Writes binary output at path to stdout
+fn copy_to_stdout(path: &str) -> Result<(), Error> {
+    let bytes: Vec<u8> = fs::read(path)
+        .map_err(|e| Error::new(ErrorType::ReadFile(path, e.to_string()), 0))?;
+    write_output("-", &bytes)
+}
+
+/// Name of an input in line markers
+fn display_name(input: &str) -> &str {
+    if input == "-" {
+        "<stdin>"
+    } else {
+        input
+    }
+}
+
+/// Write the requested dumps of one C file to stdout
+fn dump(session: &Session, processed: &str) -> Result<(), Error> {
+    let options: &Options = session.options();
+    let mut res: String = String::new();
+    if let Some(format) = options.dump_tokens {
+        res.push_str(dump::dump_tokens(&session.lex(processed)?, format).as_str());
+    }
+
+    if options.dump_ast.is_some() || options.dump_scope.is_some() {
+        let root: Node = session.parse(processed)?;
+
+        if let Some(format) = options.dump_ast {
+            res.push_str(dump::dump_ast(&root, format)?.as_str());
+        }
+
+        // Offsets are computed while generating
+        if let Some(format) = options.dump_scope {
+            let mut generator: Gen = session.generator();
+            generator.gen(&root)?;
+            res.push_str(dump::dump_scope(generator.scope(), format)?.as_str());
+        }
+    }
+
+    write_output("-", res.as_bytes())
+}
+
+/// Run the source stages on a C file.
+/// Returns the generated assembly, or None if the options stop before it.
+fn compile(session: &Session, processed: &str, output: &str) -> Result<Option<String>, Error> {
+    let options: &Options = session.options();
+
+    if options.dumps() {
+        dump(session, processed)?;
+        return Ok(None);
+    }
+
+    if options.stage == Stage::Preprocess {
+        write_output(output, processed.as_bytes())?;
+        return Ok(None);
+    }
+
+    let root: Node = session.parse(processed)?;
+    let result: String = session.generate(&root)?;
+    match options.stage {
+        Stage::Preprocess | Stage::SyntaxOnly => Ok(None),
+        Stage::Assembly => {
+            write_output(output, result.as_bytes())?;
+            Ok(None)
+        }
+        Stage::Object | Stage::Executable => Ok(Some(result)),
+    }
+}
+
+fn drive_unit(
+    session: &Session,
+    temps: &TempDir,
+    index: usize,
+    input: &str,
+    objs: &mut Vec<String>,
+) -> Result<(), Error> {
+    let options: &Options = session.options();
+    let output: String = options
+        .output
+        .clone()
+        .unwrap_or(options.default_output(input));
+
+    let asm_path: String = match InputKind::new(input) {
+        InputKind::C => {
+            let prog: String = read_input(input)?;
+            let processed: Result<String, Error> = session.preprocess(&prog, display_name(input));
+            let result: Option<String> = match processed.clone().and_then(|x| compile(session, &x, output.as_str())) {
+                Ok(x) => x,
+                Err(e) => {
+                    // Printed or located here while the program it points into is available.
+                    // Errors in included files point into those.
+                    let e: Error = e.in_file(display_name(input));
+                    let (file, original): (&str, String) = match e.file() {
+                        Some(file) if file == display_name(input) => (file, prog),
+                        Some(file) => (file, fs::read_to_string(file).unwrap_or_default()),
+                        None => return Err(e),
+                    };
+                    // Spans past preprocessing are in the expanded lines
+                    let source: String = match &processed {
+                        Ok(processed) => preprocess::expanded_source(processed, file, &original),
+                        Err(_) => original,
+                    };
+                    if options.diagnostics_format == DiagnosticsFormat::Text {
+                        e.print(&source);
+                        return Err(e.reported());
+                    }
+                    return Err(e.locate(&source));
+                }
+            };
+
+            let Some(result) = result else {
+                return Ok(());
+            };
+
+            let asm_path: String = temps.file(index, input, "s");
+            write_output(asm_path.as_str(), result.as_bytes())?;
+            asm_path
+        }
+        InputKind::Asm => {
+            if options.dumps() || !matches!(options.stage, Stage::Object | Stage::Executable) {
+                return Ok(());
+            }
+            input.to_string()
+        }
+        InputKind::Object => {
+            objs.push(input.to_string());
+            return Ok(());
+        }
+    };
+
+    if options.stage == Stage::Object {
+        if output == "-" {
+            let obj_path: String = temps.file(index, input, "o");
+            session.assemble(asm_path.as_str(), obj_path.as_str())?;
+            copy_to_stdout(obj_path.as_str())
+        } else {
+            session.assemble(asm_path.as_str(), output.as_str())
+        }
+    } else {
+        let obj_path: String = temps.file(index, input, "o");
+        session.assemble(asm_path.as_str(), obj_path.as_str())?;
+        objs.push(obj_path);
+        Ok(())
+    }
+}
+
+/// Run a linked program for cc run, reporting how it ended on stderr.
+/// Returns the exit code.
+fn execute(options: &Options, exe_path: &str) -> Result<i32, Error> {
+    // Relative paths would be looked up in PATH
+    let exe_path: String = Path::new(".").join(exe_path).to_string_lossy().to_string();
+    let status: RunStatus = run::run(exe_path.as_str(), &options.run_args, options.timeout)?;
+    eprintln!("{}: {}", options.inputs[0], status);
+    Ok(status.exit_code())
+}
+
+/// Run every stage the options ask for, printing diagnostics as they come up.
+/// Returns the exit code.
+pub fn drive(options: Options) -> i32 {
+    let session: Session = Session::new(options);
+    let options: &Options = session.options();
+
+    let res: Result<i32, Error> = TempDir::new(options.save_temps)
+        .map_err(|msg| Error::new(ErrorType::WriteFile("temporary directory", msg), 0))
+        .and_then(|temps| {
+            let mut objs: Vec<String> = Vec::new();
+            for (i, input) in options.inputs.iter().enumerate() {
+                drive_unit(&session, &temps, i, input, &mut objs)?;
+            }
+
+            if options.stage != Stage::Executable || options.dumps() {
+                return Ok(0);
+            }
+
+            match options.output.as_deref() {
+                Some("-") => {
+                    let exe_path: String = temps.file(0, "a", "out");
+                    session.link(&objs, exe_path.as_str())?;
+                    copy_to_stdout(exe_path.as_str())?;
+                }
+                // Without -o, run links into the temporary directory
+                None if options.run => {
+                    let exe_path: String = temps.file(0, "a", "out");
+                    session.link(&objs, exe_path.as_str())?;
+                    return execute(options, exe_path.as_str());
+                }
+                output => session.link(&objs, output.unwrap_or("a.out"))?,
+            }
+
+            match options.output.as_deref() {
+                Some(exe_path) if options.run => execute(options, exe_path),
+                _ => Ok(0),
+            }
+        });
+
+    let errors: Vec<Error> = res.as_ref().err().into_iter().cloned().collect();
+    if options.diagnostics_format == DiagnosticsFormat::Text {
+        for e in errors.iter().filter(|x| !x.is_reported()) {
+            e.print("");
+        }
+    } else {
+        // Always written, so that consumers can tell a clean run from a crash
+        eprint!("{}", diagnostics::render(&errors, options.diagnostics_format));
+    }
+
+    match res {
+        Ok(code) => code,
+        Err(e) => e.exit_code(),
+    }
+}
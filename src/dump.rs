@@ -0,0 +1,386 @@
+use crate::cdefs::{CFdef, CStruct, CVardef};
+use crate::error::Error;
+use crate::json::Json;
+use crate::lexer::Token;
+use crate::node::{Dtype, Node, NodeVariant};
+use crate::scope::{Scope, ScopeLayer};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DumpFormat {
+    /// Indented, one node per line
+    Tree,
+    Json,
+}
+
+impl DumpFormat {
+    pub fn new(format: &str) -> Result<Self, String> {
+        match format {
+            "tree" => Ok(DumpFormat::Tree),
+            "json" => Ok(DumpFormat::Json),
+            _ => Err(format!("Unknown dump format '{}', expected 'tree' or 'json'.", format)),
+        }
+    }
+}
+
+fn render(json: &Json, format: DumpFormat) -> String {
+    match format {
+        DumpFormat::Json => format!("{}\n", json),
+        DumpFormat::Tree => {
+            let mut res: String = String::new();
+            match json {
+                Json::Array(values) => {
+                    for value in values {
+                        render_tree(value, None, 0, &mut res);
+                    }
+                }
+                _ => render_tree(json, None, 0, &mut res),
+            }
+            res
+        }
+    }
+}
+
+/// Objects become lines made of their "kind" and scalar fields, with nested
+/// objects and arrays indented below them under their key.
+fn render_tree(json: &Json, label: Option<&str>, depth: usize, res: &mut String) {
+    let indent: String = "  ".repeat(depth);
+    let Json::Object(fields) = json else {
+        res.push_str(format!("{}{}{}\n", indent, label.map_or(String::new(), |x| format!("{}: ", x)), json).as_str());
+        return;
+    };
+
+    let mut header: Vec<String> = Vec::new();
+    if let Some(label) = label {
+        header.push(format!("{}:", label));
+    }
+    if let Some(Json::Str(kind)) = json.get("kind") {
+        header.push(kind.clone());
+    }
+    for (key, value) in fields {
+        if matches!(key.as_str(), "kind" | "line" | "dtype") {
+            continue;
+        }
+        if matches!(value, Json::Str(_) | Json::Int(_) | Json::Bool(_)) {
+            header.push(format!("{}={}", key, value));
+        }
+    }
+    if let Some(Json::Str(dtype)) = json.get("dtype") {
+        header.push(format!(": {}", dtype));
+    }
+    if let Some(Json::Int(line)) = json.get("line") {
+        header.push(format!("(line {})", line));
+    }
+    res.push_str(format!("{}{}\n", indent, header.join(" ")).as_str());
+
+    for (key, value) in fields {
+        match value {
+            Json::Object(_) => render_tree(value, Some(key), depth + 1, res),
+            Json::Array(values) => {
+                if values.is_empty() {
+                    continue;
+                }
+                res.push_str(format!("{}  {}:\n", indent, key).as_str());
+                for value in values {
+                    render_tree(value, None, depth + 2, res);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Token stream of preprocessed source
+pub fn dump_tokens(tokens: &[Token], format: DumpFormat) -> String {
+    let tokens: Vec<Json> = tokens
+        .iter()
+        .map(|tok| {
+            Json::object(vec![
+                ("kind", Json::str(format!("{:?}", tok.ttype).as_str())),
+                ("value", Json::str(tok.value.as_str())),
+                ("line", Json::Int(tok.line as i64)),
+            ])
+        })
+        .collect();
+
+    render(&Json::Array(tokens), format)
+}
+
+/// Parsed tree, annotated with the dtype of every node that has one
+pub fn dump_ast(root: &Node, format: DumpFormat) -> Result<String, Error> {
+    let mut scope: Scope = Scope::new();
+    Ok(render(&node_json(root, &mut scope)?, format))
+}
+
+/// Functions and structs of a scope, with parameter and member offsets
+pub fn dump_scope(scope: &Scope, format: DumpFormat) -> Result<String, Error> {
+    let mut fdefs: Vec<Json> = Vec::new();
+    for fdef in scope.fdefs() {
+        fdefs.push(fdef_json(fdef, scope)?);
+    }
+
+    let mut structs: Vec<Json> = Vec::new();
+    for sdef in scope.structs() {
+        structs.push(struct_json(sdef, scope)?);
+    }
+
+    Ok(render(
+        &Json::object(vec![
+            ("kind", Json::str("Scope")),
+            ("functions", Json::Array(fdefs)),
+            ("structs", Json::Array(structs)),
+        ]),
+        format,
+    ))
+}
+
+fn fdef_json(fdef: &CFdef, scope: &Scope) -> Result<Json, Error> {
+    let NodeVariant::Fdef { name, params, body, rtype } = fdef.node.variant.as_ref() else { unreachable!() };
+
+    let mut params_json: Vec<Json> = Vec::new();
+    for (i, param) in params.iter().enumerate() {
+        params_json.push(Json::object(vec![
+            ("kind", Json::str("Param")),
+            ("name", Json::str(param.vardef_name().as_str())),
+            ("dtype", Json::str(param.dtype(scope)?.to_string().as_str())),
+            ("register", match &fdef.param_registers[i] {
+                Some(reg) => Json::str(reg),
+                None => Json::Null,
+            }),
+            ("offset", match &fdef.param_registers[i] {
+                Some(_) => Json::Null,
+                None => Json::Int(fdef.param_stack_offsets[i] as i64),
+            }),
+        ]));
+    }
+
+    Ok(Json::object(vec![
+        ("kind", Json::str("Function")),
+        ("name", Json::str(name)),
+        ("defined", Json::Bool(!matches!(body.variant.as_ref(), NodeVariant::Noop))),
+        ("dtype", Json::str(rtype.to_string().as_str())),
+        ("line", Json::Int(fdef.node.line as i64)),
+        ("params", Json::Array(params_json)),
+    ]))
+}
+
+fn struct_json(sdef: &CStruct, scope: &Scope) -> Result<Json, Error> {
+    let NodeVariant::Struct { name, fields } = sdef.node.variant.as_ref() else { unreachable!() };
+
+    let mut members: Vec<Json> = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        members.push(Json::object(vec![
+            ("kind", Json::str("Member")),
+            ("name", Json::str(field.vardef_name().as_str())),
+            ("dtype", Json::str(field.dtype(scope)?.to_string().as_str())),
+            ("offset", Json::Int(sdef.memb_stack_offsets[i] as i64)),
+        ]));
+    }
+
+    let size: i32 = Dtype::from_fields(crate::node::DtypeVariant::Struct { name: name.clone() })
+        .num_bytes(scope)?;
+    Ok(Json::object(vec![
+        ("kind", Json::str("Struct")),
+        ("name", Json::str(name)),
+        ("size", Json::Int(size as i64)),
+        ("line", Json::Int(sdef.node.line as i64)),
+        ("members", Json::Array(members)),
+    ]))
+}
+
+fn nodes_json(nodes: &[Node], scope: &mut Scope) -> Result<Json, Error> {
+    let mut res: Vec<Json> = Vec::new();
+    for n in nodes {
+        res.push(node_json(n, scope)?);
+    }
+    Ok(Json::Array(res))
+}
+
+/// Walks the tree like Gen does, so that variables, functions and structs are
+/// in scope when the dtypes of nodes referring to them are computed.
+fn node_json(n: &Node, scope: &mut Scope) -> Result<Json, Error> {
+    let mut fields: Vec<(&str, Json)> = Vec::new();
+    let kind: &str = match n.variant.as_ref() {
+        NodeVariant::Noop => "Noop",
+        NodeVariant::Cpd { values } => {
+            fields.push(("values", nodes_json(values, scope)?));
+            "Cpd"
+        }
+        NodeVariant::Str { value } => {
+            fields.push(("value", Json::str(value)));
+            "Str"
+        }
+        NodeVariant::Int { value, dtype } => {
+            // Signed values are stored sign-extended, unsigned ones may not fit in an i64
+            fields.push((
+                "value",
+                if dtype.is_unsigned() && *value > i64::MAX as u64 {
+                    Json::str(value.to_string().as_str())
+                } else {
+                    Json::Int(*value as i64)
+                },
+            ));
+            "Int"
+        }
+        NodeVariant::Char { value } => {
+            fields.push(("value", Json::str(value.to_string().as_str())));
+            "Char"
+        }
+        NodeVariant::Float { value, .. } => {
+            // Json has no floats, and the debug format round trips
+            fields.push(("value", Json::str(format!("{:?}", value).as_str())));
+            "Float"
+        }
+        NodeVariant::Fcall { name, args } => {
+            fields.push(("name", Json::str(name)));
+            fields.push(("args", nodes_json(args, scope)?));
+            "Fcall"
+        }
+        NodeVariant::Fdef { name, params, body, .. } => {
+            fields.push(("name", Json::str(name)));
+
+            // Scope switches, so no nesting
+            let prev_layer: ScopeLayer = scope.pop_layer();
+            scope.push_layer();
+            scope.push_fdef(n)?;
+            for param in params {
+                scope.push_cvardef(&CVardef::new(param, 0));
+            }
+            fields.push(("params", nodes_json(params, scope)?));
+            fields.push(("body", node_json(body, scope)?));
+            scope.pop_layer();
+            scope.push_layer_from(prev_layer);
+            "Fdef"
+        }
+        NodeVariant::Vardef { var, value, .. } => {
+            fields.push(("name", Json::str(var.var_name().as_str())));
+            fields.push(("value", node_json(value, scope)?));
+            scope.push_vardef(n);
+            "Vardef"
+        }
+        NodeVariant::Var { name } => {
+            fields.push(("name", Json::str(name)));
+            "Var"
+        }
+        NodeVariant::If { cond, body, else_body } => {
+            fields.push(("cond", node_json(cond, scope)?));
+            fields.push(("body", node_json(body, scope)?));
+            fields.push(("else_body", node_json(else_body, scope)?));
+            "If"
+        }
+        NodeVariant::Return { value } => {
+            fields.push(("value", node_json(value, scope)?));
+            "Return"
+        }
+        NodeVariant::Binop { btype, l, r } => {
+            fields.push(("op", Json::str(format!("{:?}", btype).as_str())));
+            fields.push(("l", node_json(l, scope)?));
+            fields.push(("r", node_json(r, scope)?));
+            "Binop"
+        }
+        NodeVariant::Unop { utype, r } => {
+            fields.push(("op", Json::str(format!("{:?}", utype).as_str())));
+            fields.push(("r", node_json(r, scope)?));
+            "Unop"
+        }
+        NodeVariant::Ternary { cond, l, r } => {
+            fields.push(("cond", node_json(cond, scope)?));
+            fields.push(("l", node_json(l, scope)?));
+            fields.push(("r", node_json(r, scope)?));
+            "Ternary"
+        }
+        NodeVariant::Postfix { ptype, l } => {
+            fields.push(("op", Json::str(format!("{:?}", ptype).as_str())));
+            fields.push(("l", node_json(l, scope)?));
+            "Postfix"
+        }
+        NodeVariant::Struct { name, fields: members } => {
+            fields.push(("name", Json::str(name)));
+            let mut members_json: Vec<Json> = Vec::new();
+            for member in members {
+                let NodeVariant::Vardef { var, .. } = member.variant.as_ref() else { unreachable!() };
+                members_json.push(Json::object(vec![
+                    ("kind", Json::str("Member")),
+                    ("name", Json::str(var.var_name().as_str())),
+                    ("dtype", Json::str(member.dtype(scope)?.to_string().as_str())),
+                    ("line", Json::Int(member.line as i64)),
+                ]));
+            }
+            fields.push(("fields", Json::Array(members_json)));
+            scope.push_struct(n)?;
+            "Struct"
+        }
+        NodeVariant::For { init, cond, inc, body } => {
+            fields.push(("init", node_json(init, scope)?));
+            fields.push(("cond", node_json(cond, scope)?));
+            fields.push(("inc", node_json(inc, scope)?));
+            fields.push(("body", node_json(body, scope)?));
+            "For"
+        }
+        NodeVariant::While { cond, body } => {
+            fields.push(("cond", node_json(cond, scope)?));
+            fields.push(("body", node_json(body, scope)?));
+            "While"
+        }
+        NodeVariant::DoWhile { body, cond } => {
+            fields.push(("body", node_json(body, scope)?));
+            fields.push(("cond", node_json(cond, scope)?));
+            "DoWhile"
+        }
+        NodeVariant::Break => "Break",
+        NodeVariant::Continue => "Continue",
+        NodeVariant::Goto { name } => {
+            fields.push(("name", Json::str(name)));
+            "Goto"
+        }
+        NodeVariant::Label { name } => {
+            fields.push(("name", Json::str(name)));
+            "Label"
+        }
+        NodeVariant::Switch { cond, body } => {
+            fields.push(("cond", node_json(cond, scope)?));
+            fields.push(("body", node_json(body, scope)?));
+            "Switch"
+        }
+        NodeVariant::Case { value } => {
+            fields.push(("value", node_json(value, scope)?));
+            "Case"
+        }
+        NodeVariant::Cast { value, .. } => {
+            fields.push(("value", node_json(value, scope)?));
+            "Cast"
+        }
+        NodeVariant::InitList { fields: values, .. } => {
+            let mut values_json: Vec<Json> = Vec::new();
+            for (name, value) in values {
+                values_json.push(Json::object(vec![
+                    ("kind", Json::str("Field")),
+                    ("name", Json::str(name)),
+                    ("value", node_json(value, scope)?),
+                ]));
+            }
+            fields.push(("fields", Json::Array(values_json)));
+            "InitList"
+        }
+    };
+
+    // Struct member names in member access aren't variables, so they don't have a dtype
+    let dtype: Json = match n.variant.as_ref() {
+        NodeVariant::Noop | NodeVariant::Cpd { .. } | NodeVariant::Struct { .. } => Json::Null,
+        NodeVariant::If { .. } | NodeVariant::For { .. } | NodeVariant::While { .. } => Json::Null,
+        NodeVariant::Switch { .. } | NodeVariant::Case { .. } => Json::Null,
+        NodeVariant::DoWhile { .. } | NodeVariant::Break | NodeVariant::Continue => Json::Null,
+        NodeVariant::Goto { .. } | NodeVariant::Label { .. } => Json::Null,
+        NodeVariant::Return { .. } => Json::Null,
+        _ => n
+            .dtype(scope)
+            .map_or(Json::Null, |x| Json::str(x.to_string().as_str())),
+    };
+
+    let mut res: Vec<(&str, Json)> = vec![("kind", Json::str(kind))];
+    if !matches!(dtype, Json::Null) {
+        res.push(("dtype", dtype));
+    }
+    res.push(("line", Json::Int(n.line as i64)));
+    res.extend(fields);
+    Ok(Json::object(res))
+}
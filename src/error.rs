@@ -1,15 +1,32 @@
 use crate::lexer::TokenType;
 use crate::node::{Dtype, Node, NodeVariant};
+use crate::span::Span;
 use colored::Colorize;
+use std::fmt;
 
 #[derive(Debug)]
 pub enum ErrorType<'a> {
     /// Token
     UnrecognizedToken(char),
+    /// Literal
+    InvalidIntegerLiteral(&'a str),
+    /// Literal
+    IntegerLiteralTooLarge(&'a str),
+    /// Literal
+    InvalidFloatLiteral(&'a str),
+    /// Literal
+    FloatLiteralTooLarge(&'a str),
     /// Received, expected
     UnexpectedToken(TokenType, TokenType),
+    /// Keyword
+    KeywordAsIdentifier(&'a str),
+    /// Keyword
+    UnsupportedKeyword(&'a str),
+    ElseWithoutIf,
     /// Vardef name
     VardefNoExpression(&'a str),
+    /// Type of a definition without an initializer
+    NoDefaultValue(Dtype),
     /// Struct name, member
     NonexistentStructMember(&'a str, &'a str),
     /// To be used for str -> dtype.
@@ -41,18 +58,138 @@ pub enum ErrorType<'a> {
     InvalidAddressof(&'a NodeVariant),
     /// Data type of deref target
     InvalidDeref(&'a NodeVariant),
+    /// Not a variable, member or dereference
+    InvalidLvalue,
+    /// Arm types of ?:
+    TernaryTypeMismatch(Dtype, Dtype),
+    /// Type of the controlling expression
+    SwitchNotInteger(Dtype),
+    CaseNotConstant,
+    /// "case" or "default"
+    CaseOutsideSwitch(&'a str),
+    /// Case value
+    DuplicateCase(String),
+    DuplicateDefault,
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    /// Label name
+    UndefinedLabel(&'a str),
+    /// Label name
+    DuplicateLabel(&'a str),
+    /// Operator, left operand type, right operand type
+    InvalidOperands(TokenType, Dtype, Dtype),
+    /// Unary operator, operand type
+    InvalidOperand(TokenType, Dtype),
+    UnterminatedComment,
+    /// Opening quote
+    UnterminatedLiteral(char),
+    /// Escape sequence
+    InvalidEscape(&'a str),
+    /// Escape sequence
+    EscapeOutOfRange(&'a str),
+    /// Decoded contents
+    InvalidCharLiteral(&'a str),
+    /// Directive name
+    UnknownDirective(&'a str),
+    EndifWithoutIf,
+    /// Included file name
+    IncludeNotFound(&'a str),
+    /// Path, reason
+    ReadFile(&'a str, String),
+    /// Path, reason
+    WriteFile(&'a str, String),
+    /// Tool name, reason
+    ToolSpawn(&'a str, String),
+    /// Tool name, exit status (None if killed by a signal), tool output
+    ToolFailed(&'a str, Option<i32>, String),
 }
 
 impl<'a> ErrorType<'a> {
+    /// Variant name, stable for tools matching on diagnostics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ErrorType::UnrecognizedToken(..) => "UnrecognizedToken",
+            ErrorType::InvalidIntegerLiteral(..) => "InvalidIntegerLiteral",
+            ErrorType::IntegerLiteralTooLarge(..) => "IntegerLiteralTooLarge",
+            ErrorType::InvalidFloatLiteral(..) => "InvalidFloatLiteral",
+            ErrorType::FloatLiteralTooLarge(..) => "FloatLiteralTooLarge",
+            ErrorType::UnexpectedToken(..) => "UnexpectedToken",
+            ErrorType::KeywordAsIdentifier(..) => "KeywordAsIdentifier",
+            ErrorType::UnsupportedKeyword(..) => "UnsupportedKeyword",
+            ErrorType::ElseWithoutIf => "ElseWithoutIf",
+            ErrorType::VardefNoExpression(..) => "VardefNoExpression",
+            ErrorType::NoDefaultValue(..) => "NoDefaultValue",
+            ErrorType::NonexistentStructMember(..) => "NonexistentStructMember",
+            ErrorType::InvalidDtypeFromStr(..) => "InvalidDtypeFromStr",
+            ErrorType::FunctionArgParamMismatch(..) => "FunctionArgParamMismatch",
+            ErrorType::AssignTypeMismatch(..) => "AssignTypeMismatch",
+            ErrorType::StructMemberVarNonId(..) => "StructMemberVarNonId",
+            ErrorType::PrimitiveMemberAccess(..) => "PrimitiveMemberAccess",
+            ErrorType::FunctionDeclDefMismatch(..) => "FunctionDeclDefMismatch",
+            ErrorType::DuplicateFdef(..) => "DuplicateFdef",
+            ErrorType::DuplicateSdef(..) => "DuplicateSdef",
+            ErrorType::NonexistentFunction(..) => "NonexistentFunction",
+            ErrorType::NonexistentStruct(..) => "NonexistentStruct",
+            ErrorType::NonexistentVariable(..) => "NonexistentVariable",
+            ErrorType::InvalidAddressof(..) => "InvalidAddressof",
+            ErrorType::InvalidDeref(..) => "InvalidDeref",
+            ErrorType::InvalidLvalue => "InvalidLvalue",
+            ErrorType::TernaryTypeMismatch(..) => "TernaryTypeMismatch",
+            ErrorType::SwitchNotInteger(..) => "SwitchNotInteger",
+            ErrorType::CaseNotConstant => "CaseNotConstant",
+            ErrorType::CaseOutsideSwitch(..) => "CaseOutsideSwitch",
+            ErrorType::DuplicateCase(..) => "DuplicateCase",
+            ErrorType::DuplicateDefault => "DuplicateDefault",
+            ErrorType::BreakOutsideLoop => "BreakOutsideLoop",
+            ErrorType::ContinueOutsideLoop => "ContinueOutsideLoop",
+            ErrorType::UndefinedLabel(..) => "UndefinedLabel",
+            ErrorType::DuplicateLabel(..) => "DuplicateLabel",
+            ErrorType::InvalidOperands(..) => "InvalidOperands",
+            ErrorType::InvalidOperand(..) => "InvalidOperand",
+            ErrorType::UnterminatedComment => "UnterminatedComment",
+            ErrorType::UnterminatedLiteral(..) => "UnterminatedLiteral",
+            ErrorType::InvalidEscape(..) => "InvalidEscape",
+            ErrorType::EscapeOutOfRange(..) => "EscapeOutOfRange",
+            ErrorType::InvalidCharLiteral(..) => "InvalidCharLiteral",
+            ErrorType::UnknownDirective(..) => "UnknownDirective",
+            ErrorType::EndifWithoutIf => "EndifWithoutIf",
+            ErrorType::IncludeNotFound(..) => "IncludeNotFound",
+            ErrorType::ReadFile(..) => "ReadFile",
+            ErrorType::WriteFile(..) => "WriteFile",
+            ErrorType::ToolSpawn(..) => "ToolSpawn",
+            ErrorType::ToolFailed(..) => "ToolFailed",
+        }
+    }
+
     pub fn message(&self) -> String {
         match self {
             ErrorType::UnrecognizedToken(tok) => format!("Unrecognized token '{}'.", tok),
+            ErrorType::InvalidIntegerLiteral(literal) => {
+                format!("Invalid integer literal '{}'.", literal)
+            }
+            ErrorType::IntegerLiteralTooLarge(literal) => {
+                format!("Integer literal '{}' is too large for any integer type.", literal)
+            }
+            ErrorType::InvalidFloatLiteral(literal) => {
+                format!("Invalid floating literal '{}'.", literal)
+            }
+            ErrorType::FloatLiteralTooLarge(literal) => {
+                format!("Floating literal '{}' is too large for its type.", literal)
+            }
             ErrorType::UnexpectedToken(recv, expect) => {
                 format!("Expected {:?}, received {:?}.", expect, recv)
             }
+            ErrorType::KeywordAsIdentifier(keyword) => {
+                format!("'{}' is a keyword and can't be used as an identifier.", keyword)
+            }
+            ErrorType::UnsupportedKeyword(keyword) => format!("Keyword '{}' is not supported yet.", keyword),
+            ErrorType::ElseWithoutIf => String::from("else without if."),
             ErrorType::VardefNoExpression(name) => {
                 format!("Definition of variable '{}' has no expression.", name)
             }
+            ErrorType::NoDefaultValue(dtype) => {
+                format!("Definitions of type '{}' without an initializer are not supported yet.", dtype)
+            }
             ErrorType::NonexistentStructMember(sname, member) => {
                 format!("Struct '{}' has no member '{}'.", sname, member)
             }
@@ -87,50 +224,251 @@ impl<'a> ErrorType<'a> {
             ErrorType::NonexistentVariable(name) => format!("Variable '{}' does not exist.", name),
             ErrorType::InvalidAddressof(dtype) => format!("Can't take address of '{:?}'.", dtype),
             ErrorType::InvalidDeref(dtype) => format!("Can't dereference '{:?}'.", dtype),
+            ErrorType::InvalidLvalue => String::from("Expression is not an lvalue."),
+            ErrorType::TernaryTypeMismatch(l, r) => {
+                format!("Arms of ?: have incompatible types '{}' and '{}'.", l, r)
+            }
+            ErrorType::SwitchNotInteger(dtype) => {
+                format!("Switch on type '{}', which is not an integer type.", dtype)
+            }
+            ErrorType::CaseNotConstant => String::from("Case value is not an integer constant expression."),
+            ErrorType::CaseOutsideSwitch(label) => format!("'{}' label not within a switch.", label),
+            ErrorType::DuplicateCase(value) => format!("Duplicate case value {}.", value),
+            ErrorType::DuplicateDefault => String::from("Multiple default labels in one switch."),
+            ErrorType::BreakOutsideLoop => String::from("'break' not within a loop or switch."),
+            ErrorType::ContinueOutsideLoop => String::from("'continue' not within a loop."),
+            ErrorType::UndefinedLabel(name) => format!("Label '{}' used but not defined.", name),
+            ErrorType::DuplicateLabel(name) => format!("Duplicate label '{}'.", name),
+            ErrorType::InvalidOperands(op, l, r) => {
+                format!("Invalid operands to {:?}, '{}' and '{}'.", op, l, r)
+            }
+            ErrorType::InvalidOperand(op, dtype) => {
+                format!("Invalid operand to {:?}, '{}'.", op, dtype)
+            }
+            ErrorType::UnterminatedComment => String::from("Unterminated /* comment."),
+            ErrorType::UnterminatedLiteral(quote) => format!(
+                "Unterminated {} literal.",
+                if *quote == '"' { "string" } else { "character" }
+            ),
+            ErrorType::InvalidEscape(seq) => format!("Invalid escape sequence '{}'.", seq),
+            ErrorType::EscapeOutOfRange(seq) => {
+                format!("Escape sequence '{}' is out of range.", seq)
+            }
+            ErrorType::InvalidCharLiteral(value) => format!(
+                "Character literal must contain exactly one byte, found {}.",
+                value.chars().count()
+            ),
+            ErrorType::UnknownDirective(name) => {
+                format!("Unknown preprocessor directive '#{}'.", name)
+            }
+            ErrorType::EndifWithoutIf => String::from("#endif without #if."),
+            ErrorType::IncludeNotFound(name) => format!("Included file '{}' not found.", name),
+            ErrorType::ReadFile(path, reason) => format!("Can't read file '{}': {}.", path, reason),
+            ErrorType::WriteFile(path, reason) => {
+                format!("Unable to write to file '{}': {}.", path, reason)
+            }
+            ErrorType::ToolSpawn(tool, reason) => format!("Unable to run '{}': {}.", tool, reason),
+            ErrorType::ToolFailed(tool, status, output) => format!(
+                "{}{}",
+                output,
+                match status {
+                    Some(code) => format!("'{}' returned {} exit status.", tool, code),
+                    None => format!("'{}' was terminated by a signal.", tool),
+                }
+            ),
         }
     }
 }
 
-#[derive(Debug)]
+/// Related location attached to an error, such as a previous definition
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub message: String,
+    /// None if unknown, the note is then in the error's file
+    pub file: Option<String>,
+    pub line: usize,
+    pub span: Option<Span>,
+}
+
+/// Owned diagnostic. Line 0 means the error isn't tied to a source line.
+/// Boxed to keep results small.
+#[derive(Debug, Clone)]
 pub struct Error {
+    inner: Box<ErrorInner>,
+}
+
+#[derive(Debug, Clone)]
+struct ErrorInner {
+    kind: &'static str,
     message: String,
+    /// None until whoever knows which file is being compiled fills it in
+    file: Option<String>,
     line: usize,
+    /// Offending range, underlined when printed
+    span: Option<Span>,
+    notes: Vec<Note>,
+    /// Exit status of a failed external tool
+    status: Option<i32>,
+    /// Already printed by whoever had the program it points into
+    reported: bool,
 }
 
 impl Error {
     pub fn new(etype: ErrorType, line: usize) -> Self {
-        Self {
+        let inner: ErrorInner = ErrorInner {
+            kind: etype.kind(),
             message: etype.message(),
+            file: None,
             line,
+            span: None,
+            notes: Vec::new(),
+            status: match etype {
+                ErrorType::ToolFailed(_, status, _) => status,
+                _ => None,
+            },
+            reported: false,
+        };
+        Self { inner: Box::new(inner) }
+    }
+
+    /// Point at span, which also decides the line and file
+    pub fn with_span(mut self, span: Span) -> Self {
+        if !span.is_empty() {
+            if !span.file.is_empty() {
+                self.inner.file = Some(span.file.to_string());
+            }
+            self.inner.line = span.line;
+            self.inner.span = Some(span);
+        }
+        self
+    }
+
+    /// Point at span unless a more precise one is already known.
+    /// Spans on another line than the error are ignored.
+    pub fn or_span(self, span: Span) -> Self {
+        if self.inner.span.is_none() && span.line == self.inner.line {
+            self.with_span(span)
+        } else {
+            self
+        }
+    }
+
+    pub fn with_note(mut self, message: &str, span: Span) -> Self {
+        self.inner.notes.push(Note {
+            message: message.to_string(),
+            file: Some(span.file.to_string()).filter(|x| !x.is_empty()),
+            line: span.line,
+            span: Some(span).filter(|x| !x.is_empty()),
+        });
+        self
+    }
+
+    /// Set the file the error points into, unless it's already known
+    pub fn in_file(mut self, file: &str) -> Self {
+        if self.inner.file.is_none() && self.inner.line != 0 {
+            self.inner.file = Some(file.to_string());
         }
+        self
     }
 
+    /// Fill in missing spans with the whole of their line in prog, minus indentation.
+    /// prog is the source of the error's file.
+    pub fn locate(mut self, prog: &str) -> Self {
+        let lines: Vec<&str> = prog.split('\n').collect();
+        let whole_line = |line: usize| -> Option<Span> {
+            let text: &str = lines.get(line.checked_sub(1)?)?;
+            let indent: usize = text.len() - text.trim_start().len();
+            Some(Span {
+                line,
+                column: indent + 1,
+                end_line: line,
+                end_column: text.trim_end().len().max(indent) + 1,
+                ..Span::default()
+            })
+        };
+
+        if self.inner.span.is_none() {
+            self.inner.span = whole_line(self.inner.line);
+        }
+        for note in &mut self.inner.notes {
+            if note.span.is_none() && (note.file.is_none() || note.file == self.inner.file) {
+                note.span = whole_line(note.line);
+            }
+        }
+        self
+    }
+
+    pub fn reported(mut self) -> Self {
+        self.inner.reported = true;
+        self
+    }
+
+    pub fn is_reported(&self) -> bool {
+        self.inner.reported
+    }
+
+    /// ErrorType variant name
+    pub fn kind(&self) -> &'static str {
+        self.inner.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.inner.message
+    }
+
+    pub fn file(&self) -> Option<&str> {
+        self.inner.file.as_deref()
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.inner.span
+    }
+
+    pub fn notes(&self) -> &[Note] {
+        &self.inner.notes
+    }
+
+    pub fn line(&self) -> usize {
+        self.inner.line
+    }
+
+    /// Exit status the driver should return
+    pub fn exit_code(&self) -> i32 {
+        self.inner.status.unwrap_or(1)
+    }
+
+    /// prog is the source of the error's file
     pub fn print(&self, prog: &str) {
+        if self.inner.line == 0 {
+            eprintln!("{}: {}", "error".bright_red(), self.inner.message);
+            return;
+        }
+
         let split: Vec<&str> = prog.split('\n').collect();
-        println!(
-            "{}: Line {}: {}",
+        eprintln!(
+            "{}: {}{}",
             "error".bright_red(),
-            self.line,
-            self.message
+            location(self.file(), self.inner.line),
+            self.inner.message
         );
-        let longest: usize = *[if self.line == 0 { 0 } else { self.line - 1 }, self.line, self.line + 1]
+        let longest: usize = *[if self.inner.line == 0 { 0 } else { self.inner.line - 1 }, self.inner.line, self.inner.line + 1]
             .map(|x| x.to_string().len())
             .iter()
             .max()
             .unwrap();
         for i in -1i32..=1 {
-            if (self.line as i32 + i - 1) < 0 || (i + self.line as i32) < 0 || (i + self.line as i32) >= split.len() as i32 {
+            if (self.inner.line as i32 + i - 1) < 0 || (i + self.inner.line as i32) < 0 || (i + self.inner.line as i32) >= split.len() as i32 {
                 continue;
             }
 
-            let padding: usize = longest - (self.line as i32 + i).to_string().len();
+            let padding: usize = longest - (self.inner.line as i32 + i).to_string().len();
             let format: String = format!(
                 "  {}{} | {}",
-                self.line as i32 + i,
+                self.inner.line as i32 + i,
                 " ".repeat(padding),
-                split[(self.line as i32 + i - 1) as usize]
+                split[(self.inner.line as i32 + i - 1) as usize]
             );
-            println!(
+            eprintln!(
                 "{}",
                 if i == 0 {
                     format.white().bold()
@@ -138,6 +476,65 @@ impl Error {
                     format.truecolor(150, 150, 150)
                 }
             );
+
+            if i == 0 {
+                if let Some(span) = self.inner.span {
+                    let text: &str = split[self.inner.line - 1];
+                    eprintln!("  {} | {}", " ".repeat(longest), underline(text, span).bright_red());
+                }
+            }
+        }
+
+        for note in &self.inner.notes {
+            // Only the error's file is available
+            let elsewhere: bool = note.file.is_some() && note.file != self.inner.file;
+            eprintln!("{}: {}{}", "note".bright_cyan(), location(note.file.as_deref().or(self.file()), note.line), note.message);
+            if let Some(text) = note.line.checked_sub(1).and_then(|x| split.get(x)).filter(|_| !elsewhere) {
+                let width: usize = note.line.to_string().len();
+                eprintln!("{}", format!("  {} | {}", note.line, text).truecolor(150, 150, 150));
+                if let Some(span) = note.span {
+                    eprintln!("  {} | {}", " ".repeat(width), underline(text, span).bright_cyan());
+                }
+            }
+        }
+    }
+}
+
+/// "<file>: Line <line>: " prefix of printed messages
+fn location(file: Option<&str>, line: usize) -> String {
+    match file {
+        Some(file) => format!("{}: Line {}: ", file, line),
+        None => format!("Line {}: ", line),
+    }
+}
+
+/// "^~~~" under the part of text covered by span, up to the end of the line
+/// if the span continues on the next ones. Tabs are kept so the marker lines up.
+fn underline(text: &str, span: Span) -> String {
+    let len: usize = text.chars().count();
+    let start: usize = span.column.clamp(1, len + 1);
+    let end: usize = if span.end_line == span.line {
+        span.end_column.min(len + 1)
+    } else {
+        text.trim_end().chars().count() + 1
+    };
+
+    let indent: String = text
+        .chars()
+        .take(start - 1)
+        .map(|x| if x == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!("{}^{}", indent, "~".repeat(end.saturating_sub(start + 1)))
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.inner.line == 0 {
+            write!(f, "{}", self.inner.message)
+        } else {
+            write!(f, "Line {}: {}", self.inner.line, self.inner.message)
         }
     }
 }
+
+impl std::error::Error for Error {}
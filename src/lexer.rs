@@ -1,10 +1,13 @@
 use crate::error::{Error, ErrorType};
+use crate::literal;
+use crate::span::{self, Span};
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum TokenType {
     Id,
     Str,
     Int,
+    Float,
     Char,
     Semi,
     Lparen,
@@ -29,22 +32,106 @@ pub enum TokenType {
     Arrow,
     And,
     Or,
+    Pipe,
+    Caret,
+    Tilde,
+    Shl,
+    Shr,
+    Percent,
+    Inc,
+    Dec,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    DivEqual,
+    PercentEqual,
+    AmpEqual,
+    PipeEqual,
+    CaretEqual,
+    ShlEqual,
+    ShrEqual,
+    Question,
+    Colon,
+    Keyword(Keyword),
     Eof,
 }
 
+/// C11 keywords (6.4.1), which can't be used as identifiers
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Keyword {
+    Auto,
+    Break,
+    Case,
+    Char,
+    Const,
+    Continue,
+    Default,
+    Do,
+    Double,
+    Else,
+    Enum,
+    Extern,
+    Float,
+    For,
+    Goto,
+    If,
+    Inline,
+    Int,
+    Long,
+    Register,
+    Restrict,
+    Return,
+    Short,
+    Signed,
+    Sizeof,
+    Static,
+    Struct,
+    Switch,
+    Typedef,
+    Union,
+    Unsigned,
+    Void,
+    Volatile,
+    While,
+    Alignas,
+    Alignof,
+    Atomic,
+    Bool,
+    Complex,
+    Generic,
+    Imaginary,
+    Noreturn,
+    StaticAssert,
+    ThreadLocal,
+}
+
 #[derive(Clone)]
 pub struct Token {
     pub ttype: TokenType,
     pub value: String,
     pub line: usize,
+    pub span: Span,
 }
 
-#[derive(Clone)]
-pub struct Lexer {
-    contents: String,
+pub struct Lexer<'a> {
+    contents: &'a str,
     pub line: usize,
+    /// Byte offset of ch
     index: usize,
     ch: char,
+    /// 1-based, in characters
+    column: usize,
+    /// Start of the token being lexed
+    mark: Span,
+    /// File of the last line marker
+    file: &'static str,
+}
+
+/// Lexed tokens with lookahead and backtracking.
+/// Positions are indices into the buffer, so both are O(1).
+pub struct TokenStream {
+    tokens: Vec<Token>,
+    pos: usize,
 }
 
 impl TokenType {
@@ -55,82 +142,283 @@ impl TokenType {
                 | TokenType::Minus
                 | TokenType::Star
                 | TokenType::Div
+                | TokenType::Percent
+                | TokenType::PlusEqual
+                | TokenType::MinusEqual
+                | TokenType::StarEqual
+                | TokenType::DivEqual
+                | TokenType::PercentEqual
+                | TokenType::AmpEqual
+                | TokenType::PipeEqual
+                | TokenType::CaretEqual
+                | TokenType::ShlEqual
+                | TokenType::ShrEqual
                 | TokenType::Less
                 | TokenType::Greater
                 | TokenType::LessEqual
                 | TokenType::GreaterEqual
                 | TokenType::EqualCmp
                 | TokenType::NotEqual
-                | TokenType::Dot
                 | TokenType::Equal
                 | TokenType::And
                 | TokenType::Or
-                | TokenType::Arrow
+                | TokenType::Amp
+                | TokenType::Pipe
+                | TokenType::Caret
+                | TokenType::Shl
+                | TokenType::Shr
+                | TokenType::Question
         )
     }
 
     /// High weight binops will be the operands of low weight binops.
+    /// The weights are C's precedence levels, postfix (15) and unary (14) operators are
+    /// parsed with their operand, and the comma (1) only where commas don't separate a list.
     pub fn binop_weight(&self) -> i32 {
         match self {
-            TokenType::Dot | TokenType::Arrow => 3,
-            TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Div => 2,
-            TokenType::Less
-            | TokenType::Greater
-            | TokenType::LessEqual
-            | TokenType::GreaterEqual
-            | TokenType::EqualCmp
-            | TokenType::NotEqual
-            | TokenType::Equal => 1,
-            TokenType::And | TokenType::Or => 0,
+            TokenType::Star | TokenType::Div | TokenType::Percent => 13,
+            TokenType::Plus | TokenType::Minus => 12,
+            TokenType::Shl | TokenType::Shr => 11,
+            TokenType::Less | TokenType::Greater | TokenType::LessEqual | TokenType::GreaterEqual => 10,
+            TokenType::EqualCmp | TokenType::NotEqual => 9,
+            TokenType::Amp => 8,
+            TokenType::Caret => 7,
+            TokenType::Pipe => 6,
+            TokenType::And => 5,
+            TokenType::Or => 4,
+            TokenType::Question => 3,
+            TokenType::Equal => 2,
+            _ if self.compound_base().is_some() => 2,
+            TokenType::Comma => 1,
             _ => panic!(),
         }
     }
 
+    /// Assignments and ?: group to the right, `a = b = c` is `a = (b = c)`
+    pub fn is_right_assoc(&self) -> bool {
+        self.binop_weight() <= TokenType::Question.binop_weight() && *self != TokenType::Comma
+    }
+
+    /// Operator applied by a compound assignment, Plus for PlusEqual
+    pub fn compound_base(&self) -> Option<TokenType> {
+        match self {
+            TokenType::PlusEqual => Some(TokenType::Plus),
+            TokenType::MinusEqual => Some(TokenType::Minus),
+            TokenType::StarEqual => Some(TokenType::Star),
+            TokenType::DivEqual => Some(TokenType::Div),
+            TokenType::PercentEqual => Some(TokenType::Percent),
+            TokenType::AmpEqual => Some(TokenType::Amp),
+            TokenType::PipeEqual => Some(TokenType::Pipe),
+            TokenType::CaretEqual => Some(TokenType::Caret),
+            TokenType::ShlEqual => Some(TokenType::Shl),
+            TokenType::ShrEqual => Some(TokenType::Shr),
+            _ => None,
+        }
+    }
+
+    /// Dot, Arrow, Inc and Dec after an operand
+    pub fn is_postfix(&self) -> bool {
+        matches!(self, TokenType::Dot | TokenType::Arrow | TokenType::Inc | TokenType::Dec)
+    }
+
+    /// Where an operand is expected, a binop is taken as a unary operator
     pub fn is_unop(&self) -> bool {
-        matches!(self, TokenType::Star | TokenType::Amp | TokenType::Not)
+        matches!(
+            self,
+            TokenType::Star
+                | TokenType::Amp
+                | TokenType::Not
+                | TokenType::Tilde
+                | TokenType::Minus
+                | TokenType::Plus
+                | TokenType::Inc
+                | TokenType::Dec
+        )
+    }
+
+    /// Operators taking integer operands only
+    pub fn is_bitwise(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Amp | TokenType::Pipe | TokenType::Caret | TokenType::Shl | TokenType::Shr
+        )
+    }
+}
+
+impl Keyword {
+    pub fn new(id: &str) -> Option<Self> {
+        Some(match id {
+            "auto" => Keyword::Auto,
+            "break" => Keyword::Break,
+            "case" => Keyword::Case,
+            "char" => Keyword::Char,
+            "const" => Keyword::Const,
+            "continue" => Keyword::Continue,
+            "default" => Keyword::Default,
+            "do" => Keyword::Do,
+            "double" => Keyword::Double,
+            "else" => Keyword::Else,
+            "enum" => Keyword::Enum,
+            "extern" => Keyword::Extern,
+            "float" => Keyword::Float,
+            "for" => Keyword::For,
+            "goto" => Keyword::Goto,
+            "if" => Keyword::If,
+            "inline" => Keyword::Inline,
+            "int" => Keyword::Int,
+            "long" => Keyword::Long,
+            "register" => Keyword::Register,
+            "restrict" => Keyword::Restrict,
+            "return" => Keyword::Return,
+            "short" => Keyword::Short,
+            "signed" => Keyword::Signed,
+            "sizeof" => Keyword::Sizeof,
+            "static" => Keyword::Static,
+            "struct" => Keyword::Struct,
+            "switch" => Keyword::Switch,
+            "typedef" => Keyword::Typedef,
+            "union" => Keyword::Union,
+            "unsigned" => Keyword::Unsigned,
+            "void" => Keyword::Void,
+            "volatile" => Keyword::Volatile,
+            "while" => Keyword::While,
+            "_Alignas" => Keyword::Alignas,
+            "_Alignof" => Keyword::Alignof,
+            "_Atomic" => Keyword::Atomic,
+            "_Bool" => Keyword::Bool,
+            "_Complex" => Keyword::Complex,
+            "_Generic" => Keyword::Generic,
+            "_Imaginary" => Keyword::Imaginary,
+            "_Noreturn" => Keyword::Noreturn,
+            "_Static_assert" => Keyword::StaticAssert,
+            "_Thread_local" => Keyword::ThreadLocal,
+            _ => return None,
+        })
+    }
+
+    /// Keywords that can start a supported data type
+    pub fn is_dtype(&self) -> bool {
+        matches!(
+            self,
+            Keyword::Int
+                | Keyword::Char
+                | Keyword::Signed
+                | Keyword::Unsigned
+                | Keyword::Long
+                | Keyword::Float
+                | Keyword::Double
+                | Keyword::Void
+                | Keyword::Struct
+        )
+    }
+
+    /// Keywords that can follow another in an arithmetic type, like "unsigned long int"
+    pub fn is_arithmetic_specifier(&self) -> bool {
+        matches!(
+            self,
+            Keyword::Int
+                | Keyword::Char
+                | Keyword::Signed
+                | Keyword::Unsigned
+                | Keyword::Long
+                | Keyword::Float
+                | Keyword::Double
+        )
     }
 }
 
 impl Token {
+    /// The lexer fills in the span
     pub fn new(ttype: TokenType, value: String, line: usize) -> Self {
-        Self { ttype, value, line }
+        Self {
+            ttype,
+            value,
+            line,
+            span: Span::default(),
+        }
     }
 }
 
-impl Lexer {
-    pub fn new(contents: &str) -> Self {
+impl<'a> Lexer<'a> {
+    pub fn new(contents: &'a str) -> Self {
         Self {
-            contents: contents.to_string(),
+            contents,
             line: 1,
             index: 0,
-            ch: contents.chars().next().unwrap(),
+            ch: contents.chars().next().unwrap_or('\0'),
+            column: 1,
+            mark: Span::default(),
+            file: "",
+        }
+    }
+
+    /// All tokens of contents, ending with Eof
+    pub fn tokenize(contents: &str) -> Result<Vec<Token>, Error> {
+        let mut lexer: Lexer = Lexer::new(contents);
+        let mut tokens: Vec<Token> = Vec::new();
+        loop {
+            let tok: Token = lexer.next()?;
+            let eof: bool = tok.ttype == TokenType::Eof;
+            tokens.push(tok);
+
+            if eof {
+                return Ok(tokens);
+            }
         }
     }
 
+    /// Next token, spanning the source it was lexed from
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Token, Error> {
-        while self.index < self.contents.len() - 1 {
+        let res: Result<Token, Error> = self.lex();
+        let span: Span = self.mark.to(self.position());
+        match res {
+            Ok(mut tok) => {
+                tok.span = span;
+                Ok(tok)
+            }
+            Err(e) if self.file.is_empty() => Err(e.or_span(span)),
+            Err(e) => Err(e.or_span(span).in_file(self.file)),
+        }
+    }
+
+    fn lex(&mut self) -> Result<Token, Error> {
+        while self.index < self.contents.len() {
             while self.ch.is_whitespace() && self.ch != '\n' {
                 self.advance();
             }
+            if self.index >= self.contents.len() {
+                break;
+            }
+            self.mark = self.position();
 
-            if self.ch.is_numeric() {
-                return Ok(Token::new(TokenType::Int, self.collect_num(), self.line));
+            if self.ch.is_numeric() || (self.ch == '.' && self.lookahead().is_some_and(|x| x.is_ascii_digit())) {
+                let num: String = self.collect_num();
+                let ttype: TokenType = if literal::is_float(&num) { TokenType::Float } else { TokenType::Int };
+                return Ok(Token::new(ttype, num, self.line));
             }
 
-            if self.ch.is_alphabetic() {
-                return Ok(Token::new(TokenType::Id, self.collect_id(), self.line));
+            if self.ch.is_alphabetic() || self.ch == '_' {
+                let id: String = self.collect_id();
+                let ttype: TokenType = match Keyword::new(&id) {
+                    Some(keyword) => TokenType::Keyword(keyword),
+                    None => TokenType::Id,
+                };
+                return Ok(Token::new(ttype, id, self.line));
             }
 
             if self.ch == '"' {
-                return Ok(Token::new(TokenType::Str, self.collect_str(), self.line));
+                let line: usize = self.line;
+                return Ok(Token::new(TokenType::Str, self.collect_literal('"')?, line));
             }
 
             if self.ch == '\'' {
-                self.advance();
-                let ch: char = self.ch;
-                self.advance();
-                self.advance();
-                return Ok(Token::new(TokenType::Char, ch.to_string(), self.line));
+                let line: usize = self.line;
+                let value: String = self.collect_literal('\'')?;
+                if value.chars().count() != 1 {
+                    return Err(Error::new(ErrorType::InvalidCharLiteral(value.as_str()), line));
+                }
+                return Ok(Token::new(TokenType::Char, value, line));
             }
 
             match self.ch {
@@ -148,11 +436,22 @@ impl Lexer {
                     }
                 }
                 ',' => return Ok(self.advance_with_tok(TokenType::Comma)),
-                '*' => return Ok(self.advance_with_tok(TokenType::Star)),
+                '?' => return Ok(self.advance_with_tok(TokenType::Question)),
+                ':' => return Ok(self.advance_with_tok(TokenType::Colon)),
+                '*' => {
+                    self.advance();
+                    return Ok(self.with_equal("*", TokenType::Star, TokenType::StarEqual));
+                }
+                '%' => {
+                    self.advance();
+                    return Ok(self.with_equal("%", TokenType::Percent, TokenType::PercentEqual));
+                }
                 '&' => {
                     self.advance();
                     if self.ch == '&' {
                         return Ok(self.advance_with_tok(TokenType::And));
+                    } else if self.ch == '=' {
+                        return Ok(self.advance_with_tok(TokenType::AmpEqual));
                     } else {
                         return Ok(Token::new(TokenType::Amp, String::from("&"), self.line));
                     }
@@ -161,24 +460,55 @@ impl Lexer {
                     self.advance();
                     if self.ch == '|' {
                         return Ok(self.advance_with_tok(TokenType::Or));
+                    } else if self.ch == '=' {
+                        return Ok(self.advance_with_tok(TokenType::PipeEqual));
                     } else {
-                        return Err(Error::new(ErrorType::UnrecognizedToken(self.ch), self.line));
+                        return Ok(Token::new(TokenType::Pipe, String::from("|"), self.line));
+                    }
+                }
+                '^' => {
+                    self.advance();
+                    return Ok(self.with_equal("^", TokenType::Caret, TokenType::CaretEqual));
+                }
+                '~' => return Ok(self.advance_with_tok(TokenType::Tilde)),
+                '+' => {
+                    self.advance();
+                    if self.ch == '+' {
+                        return Ok(self.advance_with_tok(TokenType::Inc));
+                    } else if self.ch == '=' {
+                        return Ok(self.advance_with_tok(TokenType::PlusEqual));
+                    } else {
+                        return Ok(Token::new(TokenType::Plus, String::from("+"), self.line));
                     }
                 }
-                '+' => return Ok(self.advance_with_tok(TokenType::Plus)),
                 '-' => {
                     self.advance();
                     if self.ch == '>' {
                         return Ok(self.advance_with_tok(TokenType::Arrow));
+                    } else if self.ch == '-' {
+                        return Ok(self.advance_with_tok(TokenType::Dec));
+                    } else if self.ch == '=' {
+                        return Ok(self.advance_with_tok(TokenType::MinusEqual));
                     } else {
                         return Ok(Token::new(TokenType::Minus, String::from("-"), self.line));
                     }
                 }
-                '/' => return Ok(self.advance_with_tok(TokenType::Div)),
+                '/' => {
+                    self.advance();
+                    match self.ch {
+                        '/' => self.skip_line_comment(),
+                        '*' => self.skip_block_comment()?,
+                        '=' => return Ok(self.advance_with_tok(TokenType::DivEqual)),
+                        _ => return Ok(Token::new(TokenType::Div, String::from("/"), self.line)),
+                    }
+                }
                 '<' => {
                     self.advance();
                     if self.ch == '=' {
                         return Ok(self.advance_with_tok(TokenType::LessEqual));
+                    } else if self.ch == '<' {
+                        self.advance();
+                        return Ok(self.with_equal("<<", TokenType::Shl, TokenType::ShlEqual));
                     } else {
                         return Ok(Token::new(TokenType::Less, String::from("<"), self.line));
                     }
@@ -187,6 +517,9 @@ impl Lexer {
                     self.advance();
                     if self.ch == '=' {
                         return Ok(self.advance_with_tok(TokenType::GreaterEqual));
+                    } else if self.ch == '>' {
+                        self.advance();
+                        return Ok(self.with_equal(">>", TokenType::Shr, TokenType::ShrEqual));
                     } else {
                         return Ok(Token::new(TokenType::Greater, String::from(">"), self.line));
                     }
@@ -200,6 +533,7 @@ impl Lexer {
                     }
                 }
                 '.' => return Ok(self.advance_with_tok(TokenType::Dot)),
+                '#' if self.at_line_start() => self.skip_line_marker(),
                 '\n' => {
                     self.line += 1;
                     self.advance()
@@ -208,47 +542,216 @@ impl Lexer {
             }
         }
 
+        self.mark = self.position();
         Ok(Token::new(TokenType::Eof, String::new(), self.line))
     }
 
-    pub fn peek(&mut self, count: usize) -> Result<Token, Error> {
-        let mut copy: Lexer = self.clone();
-        for _ in 0..(count - 1) {
-            copy.next()?;
+    /// Empty span at the current character
+    fn position(&self) -> Span {
+        Span {
+            file: self.file,
+            start: self.index,
+            end: self.index,
+            line: self.line,
+            column: self.column,
+            end_line: self.line,
+            end_column: self.column,
         }
-
-        copy.next()
     }
 
     fn advance(&mut self) {
         if self.index < self.contents.len() {
-            self.index += 1;
-            self.ch = self.contents.chars().nth(self.index).unwrap();
+            if self.ch == '\n' {
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+            self.index += self.ch.len_utf8();
+            self.ch = self.contents[self.index..].chars().next().unwrap_or('\0');
         }
     }
 
-    fn collect_num(&mut self) -> String {
-        let mut res: String = String::new();
+    /// Character after ch
+    fn lookahead(&self) -> Option<char> {
+        self.contents[self.index..].chars().nth(1)
+    }
 
-        while self.ch.is_numeric() || self.ch == '.' {
-            res.push(self.ch);
+    fn at_line_start(&self) -> bool {
+        self.contents[..self.index]
+            .chars()
+            .rev()
+            .find(|x| *x != ' ' && *x != '\t')
+            .is_none_or(|x| x == '\n')
+    }
+
+    /// Preprocessor line markers, "# <line> "<file>" <flags>", set the line and file of the next line
+    fn skip_line_marker(&mut self) {
+        self.advance();
+        while self.ch == ' ' || self.ch == '\t' {
             self.advance();
         }
 
-        res
+        let line: usize = self.collect_num().parse::<usize>().unwrap_or(self.line + 1);
+        while self.ch == ' ' || self.ch == '\t' {
+            self.advance();
+        }
+
+        if self.ch == '"' {
+            self.advance();
+            let start: usize = self.index;
+            while self.ch != '"' && self.ch != '\n' && self.index < self.contents.len() {
+                self.advance();
+            }
+            self.file = span::intern_file(&self.contents[start..self.index]);
+        }
+        while self.ch != '\n' && self.index < self.contents.len() {
+            self.advance();
+        }
+
+        // The newline ending the marker increments it
+        self.line = line.saturating_sub(1);
     }
 
-    fn collect_str(&mut self) -> String {
-        let mut res: String = String::new();
+    /// Stops at the newline so it's still counted
+    fn skip_line_comment(&mut self) {
+        while self.ch != '\n' && self.index < self.contents.len() {
+            self.advance();
+        }
+    }
+
+    /// Skips past the closing */, counting the lines in between
+    fn skip_block_comment(&mut self) -> Result<(), Error> {
+        let line: usize = self.line;
         self.advance();
 
-        while self.ch != '"' {
-            res.push(self.ch);
+        while self.index < self.contents.len() {
+            if self.ch == '*' && self.lookahead() == Some('/') {
+                self.advance();
+                self.advance();
+                return Ok(());
+            }
+
+            if self.ch == '\n' {
+                self.line += 1;
+            }
             self.advance();
         }
 
+        Err(Error::new(ErrorType::UnterminatedComment, line))
+    }
+
+    /// Digits, suffixes and base prefixes, checked when the literal is parsed
+    fn collect_num(&mut self) -> String {
+        let mut res: String = String::new();
+
+        loop {
+            // Exponents can be signed, e isn't an exponent in hex literals
+            let hex: bool = res.starts_with("0x") || res.starts_with("0X");
+            let exponent: bool = if hex { res.ends_with(['p', 'P']) } else { res.ends_with(['e', 'E']) };
+            if self.ch.is_ascii_alphanumeric() || self.ch == '.' || self.ch == '_' || (exponent && matches!(self.ch, '+' | '-')) {
+                res.push(self.ch);
+                self.advance();
+            } else {
+                return res;
+            }
+        }
+    }
+
+    /// Contents of a string or char literal with escapes decoded.
+    /// Every char of the result is one byte, so multibyte characters take several.
+    fn collect_literal(&mut self, quote: char) -> Result<String, Error> {
+        let line: usize = self.line;
+        let mut res: String = String::new();
         self.advance();
-        res
+
+        loop {
+            if self.ch == '\n' || self.index >= self.contents.len() {
+                return Err(Error::new(ErrorType::UnterminatedLiteral(quote), line));
+            }
+
+            match self.ch {
+                ch if ch == quote => {
+                    self.advance();
+                    return Ok(res);
+                }
+                '\\' => {
+                    self.advance();
+                    self.collect_escape(&mut res)?;
+                }
+                ch => {
+                    push_bytes(&mut res, ch);
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    /// Escape sequence after the backslash
+    fn collect_escape(&mut self, res: &mut String) -> Result<(), Error> {
+        let simple: Option<char> = match self.ch {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            'a' => Some('\x07'),
+            'b' => Some('\x08'),
+            'f' => Some('\x0c'),
+            'v' => Some('\x0b'),
+            '\\' | '\'' | '"' | '?' => Some(self.ch),
+            _ => None,
+        };
+        if let Some(ch) = simple {
+            res.push(ch);
+            self.advance();
+            return Ok(());
+        }
+
+        let mut seq: String = format!("\\{}", self.ch);
+        let (radix, max_digits): (u32, usize) = match self.ch {
+            '0'..='7' => {
+                // The first digit is part of the value
+                seq.pop();
+                (8, 3)
+            }
+            'x' => {
+                self.advance();
+                (16, usize::MAX)
+            }
+            'u' => {
+                self.advance();
+                (16, 4)
+            }
+            'U' => {
+                self.advance();
+                (16, 8)
+            }
+            _ => return Err(Error::new(ErrorType::InvalidEscape(seq.as_str()), self.line)),
+        };
+
+        let mut value: u32 = 0;
+        let mut ndigits: usize = 0;
+        while ndigits < max_digits && self.ch.is_digit(radix) {
+            value = value.saturating_mul(radix).saturating_add(self.ch.to_digit(radix).unwrap());
+            seq.push(self.ch);
+            ndigits += 1;
+            self.advance();
+        }
+
+        let universal: bool = max_digits == 4 || max_digits == 8;
+        if ndigits == 0 || (universal && ndigits != max_digits) {
+            return Err(Error::new(ErrorType::InvalidEscape(seq.as_str()), self.line));
+        }
+
+        if universal {
+            let ch: char = char::from_u32(value)
+                .ok_or(Error::new(ErrorType::EscapeOutOfRange(seq.as_str()), self.line))?;
+            push_bytes(res, ch);
+        } else if value > 0xff {
+            return Err(Error::new(ErrorType::EscapeOutOfRange(seq.as_str()), self.line));
+        } else {
+            res.push(char::from(value as u8));
+        }
+
+        Ok(())
     }
 
     fn collect_id(&mut self) -> String {
@@ -262,9 +765,64 @@ impl Lexer {
         res
     }
 
+    /// Operator op, or its compound assignment if followed by '='.
+    /// Starts after op.
+    fn with_equal(&mut self, op: &str, ttype: TokenType, with_equal: TokenType) -> Token {
+        if self.ch == '=' {
+            self.advance_with_tok(with_equal)
+        } else {
+            Token::new(ttype, String::from(op), self.line)
+        }
+    }
+
     fn advance_with_tok(&mut self, ttype: TokenType) -> Token {
         let ch: char = self.ch;
         self.advance();
         Token::new(ttype, String::from(ch), self.line)
     }
 }
+
+/// Append the UTF-8 encoding of ch, one char per byte
+fn push_bytes(res: &mut String, ch: char) {
+    let mut buf: [u8; 4] = [0; 4];
+    for byte in ch.encode_utf8(&mut buf).bytes() {
+        res.push(char::from(byte));
+    }
+}
+
+impl TokenStream {
+    pub fn new(contents: &str) -> Result<Self, Error> {
+        Ok(Self {
+            tokens: Lexer::tokenize(contents)?,
+            pos: 0,
+        })
+    }
+
+    pub fn curr(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    /// Last token moved past, the current one at the start
+    pub fn prev(&self) -> &Token {
+        &self.tokens[self.pos.saturating_sub(1)]
+    }
+
+    /// Stays on Eof at the end
+    pub fn advance(&mut self) {
+        self.pos = (self.pos + 1).min(self.tokens.len() - 1);
+    }
+
+    /// Token count places after the current one
+    pub fn peek(&self, count: usize) -> &Token {
+        &self.tokens[(self.pos + count).min(self.tokens.len() - 1)]
+    }
+
+    /// Position to rewind to
+    pub fn checkpoint(&self) -> usize {
+        self.pos
+    }
+
+    pub fn rewind(&mut self, checkpoint: usize) {
+        self.pos = checkpoint;
+    }
+}
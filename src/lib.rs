@@ -0,0 +1,24 @@
+//! C compiler targeting x86_64 nasm.
+//!
+//! [`session::Session`] exposes every stage (preprocess, lex, parse, generate,
+//! assemble and link) separately, [`driver`] implements the command line on top of it.
+
+pub mod asm;
+pub mod cdefs;
+pub mod diagnostics;
+pub mod driver;
+pub mod dump;
+pub mod error;
+pub mod json;
+pub mod lexer;
+pub mod literal;
+pub mod node;
+pub mod parser;
+pub mod preprocess;
+pub mod run;
+pub mod scope;
+pub mod session;
+pub mod span;
+
+pub use error::Error;
+pub use session::Session;
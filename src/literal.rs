@@ -0,0 +1,118 @@
+use crate::node::DtypeVariant;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum LiteralError {
+    /// Bad digit, missing digits or unknown suffix
+    Invalid,
+    /// No type in the literal's candidate list can hold it
+    TooLarge,
+}
+
+/// Decimal literals with a '.' or an exponent, hex ones with a binary exponent
+pub fn is_float(literal: &str) -> bool {
+    let lower: String = literal.to_ascii_lowercase();
+    if lower.starts_with("0x") {
+        lower.contains(['.', 'p'])
+    } else {
+        lower.contains(['.', 'e'])
+    }
+}
+
+/// Value and C type of a floating literal (C11 6.4.4.2).
+/// Unsuffixed literals are double, f makes them float and l long double, which is double here.
+/// float values are rounded to the nearest float.
+pub fn parse_float(literal: &str) -> Result<(f64, DtypeVariant), LiteralError> {
+    let (digits, dtype): (&str, DtypeVariant) = match literal.chars().last() {
+        Some('f' | 'F') => (&literal[..literal.len() - 1], DtypeVariant::Float),
+        Some('l' | 'L') => (&literal[..literal.len() - 1], DtypeVariant::Double),
+        _ => (literal, DtypeVariant::Double),
+    };
+
+    // Hex floats aren't supported, and Rust accepts words like "inf" that C doesn't
+    if !digits.starts_with(|x: char| x.is_ascii_digit() || x == '.') || digits.to_ascii_lowercase().starts_with("0x") {
+        return Err(LiteralError::Invalid);
+    }
+    let mut value: f64 = digits.parse().map_err(|_| LiteralError::Invalid)?;
+    if dtype == DtypeVariant::Float {
+        value = value as f32 as f64;
+    }
+    if value.is_infinite() {
+        return Err(LiteralError::TooLarge);
+    }
+
+    Ok((value, dtype))
+}
+
+/// Value and C type of an integer literal (C11 6.4.4.1).
+/// The type is the first of a list, chosen by base and suffix, that can represent the value.
+pub fn parse_int(literal: &str) -> Result<(u64, DtypeVariant), LiteralError> {
+    let lower: String = literal.to_ascii_lowercase();
+    let (radix, digits_start): (u32, usize) = if lower.starts_with("0x") {
+        (16, 2)
+    } else if lower.starts_with("0b") {
+        (2, 2)
+    } else if lower.starts_with('0') && lower.len() > 1 {
+        (8, 1)
+    } else {
+        (10, 0)
+    };
+
+    let digits_end: usize = lower[digits_start..]
+        .find(|x: char| !x.is_digit(radix))
+        .map_or(lower.len(), |x| digits_start + x);
+    let digits: &str = &lower[digits_start..digits_end];
+    if digits.is_empty() && radix != 8 {
+        return Err(LiteralError::Invalid);
+    }
+
+    // Suffix letters come in any order, but ll can't be mixed case
+    let suffix: &str = &literal[digits_end..];
+    let (unsigned, longs): (bool, usize) = match suffix {
+        "" => (false, 0),
+        "u" | "U" => (true, 0),
+        "l" | "L" => (false, 1),
+        "ll" | "LL" => (false, 2),
+        "ul" | "uL" | "Ul" | "UL" | "lu" | "lU" | "Lu" | "LU" => (true, 1),
+        "ull" | "uLL" | "Ull" | "ULL" | "llu" | "llU" | "LLu" | "LLU" => (true, 2),
+        _ => return Err(LiteralError::Invalid),
+    };
+
+    let value: u64 = if digits.is_empty() {
+        // Plain 0 is octal
+        0
+    } else {
+        u64::from_str_radix(digits, radix).map_err(|_| LiteralError::TooLarge)?
+    };
+
+    // Octal, hex and binary literals can become unsigned without a suffix
+    let decimal: bool = radix == 10;
+    let candidates: &[DtypeVariant] = match (unsigned, longs, decimal) {
+        (false, 0, true) => &[DtypeVariant::Int, DtypeVariant::Long, DtypeVariant::LongLong],
+        (false, 0, false) => &[
+            DtypeVariant::Int,
+            DtypeVariant::UInt,
+            DtypeVariant::Long,
+            DtypeVariant::ULong,
+            DtypeVariant::LongLong,
+            DtypeVariant::ULongLong,
+        ],
+        (true, 0, _) => &[DtypeVariant::UInt, DtypeVariant::ULong, DtypeVariant::ULongLong],
+        (false, 1, true) => &[DtypeVariant::Long, DtypeVariant::LongLong],
+        (false, 1, false) => &[
+            DtypeVariant::Long,
+            DtypeVariant::ULong,
+            DtypeVariant::LongLong,
+            DtypeVariant::ULongLong,
+        ],
+        (true, 1, _) => &[DtypeVariant::ULong, DtypeVariant::ULongLong],
+        (false, _, true) => &[DtypeVariant::LongLong],
+        (false, _, false) => &[DtypeVariant::LongLong, DtypeVariant::ULongLong],
+        (true, _, _) => &[DtypeVariant::ULongLong],
+    };
+
+    candidates
+        .iter()
+        .find(|x| value <= x.max_value())
+        .map(|x| (value, x.clone()))
+        .ok_or(LiteralError::TooLarge)
+}
@@ -1,32 +1,127 @@
 use crate::cdefs::CStruct;
 use crate::error::{Error, ErrorType};
-use crate::lexer::TokenType;
+use crate::lexer::{Keyword, Token, TokenType};
 use crate::scope::Scope;
+use crate::span::Span;
 use std::fmt;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum DtypeVariant {
     Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
     Char,
+    UChar,
+    Float,
+    Double,
     Void,
     Struct { name: String },
 }
 
 impl DtypeVariant {
     /// Does not fill out enum variant fields, only determines the enum variant type
-    pub fn new(dtype: &str) -> Result<Self, Error> {
-        match dtype {
-            "int" => Ok(DtypeVariant::Int),
-            "char" => Ok(DtypeVariant::Char),
-            "void" => Ok(DtypeVariant::Void),
-            "struct" => Ok(DtypeVariant::Struct {
+    pub fn new(tok: &Token) -> Result<Self, Error> {
+        match tok.ttype {
+            TokenType::Keyword(Keyword::Int | Keyword::Signed) => Ok(DtypeVariant::Int),
+            TokenType::Keyword(Keyword::Unsigned) => Ok(DtypeVariant::UInt),
+            TokenType::Keyword(Keyword::Long) => Ok(DtypeVariant::Long),
+            TokenType::Keyword(Keyword::Char) => Ok(DtypeVariant::Char),
+            TokenType::Keyword(Keyword::Float) => Ok(DtypeVariant::Float),
+            TokenType::Keyword(Keyword::Double) => Ok(DtypeVariant::Double),
+            TokenType::Keyword(Keyword::Void) => Ok(DtypeVariant::Void),
+            TokenType::Keyword(Keyword::Struct) => Ok(DtypeVariant::Struct {
                 name: String::new(),
             }),
-            _ => Err(Error::new(
-                ErrorType::InvalidDtypeFromStr(dtype),
-                // TODO err line
-                0,
-            )),
+            _ => Err(Error::new(ErrorType::InvalidDtypeFromStr(tok.value.as_str()), tok.line).with_span(tok.span)),
+        }
+    }
+
+    /// Arithmetic type named by a list of specifiers such as "unsigned long int",
+    /// None if they don't name one
+    pub fn from_specifiers(specifiers: &[Keyword]) -> Option<Self> {
+        let count = |keyword: Keyword| specifiers.iter().filter(|x| **x == keyword).count();
+        let (unsigned, signed, longs, ints, chars) = (
+            count(Keyword::Unsigned),
+            count(Keyword::Signed),
+            count(Keyword::Long),
+            count(Keyword::Int),
+            count(Keyword::Char),
+        );
+        let (floats, doubles) = (count(Keyword::Float), count(Keyword::Double));
+
+        if floats + doubles > 0 {
+            // long double is the same as double
+            return match (floats, doubles, longs, specifiers.len()) {
+                (1, 0, 0, 1) => Some(DtypeVariant::Float),
+                (0, 1, 0, 1) | (0, 1, 1, 2) => Some(DtypeVariant::Double),
+                _ => None,
+            };
+        }
+        if unsigned + signed > 1 || longs > 2 || ints > 1 || chars > 1 || (chars == 1 && longs + ints > 0) {
+            return None;
+        }
+
+        Some(match (unsigned == 1, longs, chars == 1) {
+            // Plain char is signed on x86_64
+            (false, _, true) => DtypeVariant::Char,
+            (true, _, true) => DtypeVariant::UChar,
+            (false, 0, _) => DtypeVariant::Int,
+            (true, 0, _) => DtypeVariant::UInt,
+            (false, 1, _) => DtypeVariant::Long,
+            (true, 1, _) => DtypeVariant::ULong,
+            (false, _, _) => DtypeVariant::LongLong,
+            (true, _, _) => DtypeVariant::ULongLong,
+        })
+    }
+
+    pub fn is_integer(&self) -> bool {
+        !matches!(
+            self,
+            DtypeVariant::Float | DtypeVariant::Double | DtypeVariant::Void | DtypeVariant::Struct { .. }
+        )
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, DtypeVariant::Float | DtypeVariant::Double)
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, DtypeVariant::UChar | DtypeVariant::UInt | DtypeVariant::ULong | DtypeVariant::ULongLong)
+    }
+
+    /// Integer conversion rank (C11 6.3.1.1)
+    fn rank(&self) -> usize {
+        match self {
+            DtypeVariant::Char | DtypeVariant::UChar => 1,
+            DtypeVariant::Int | DtypeVariant::UInt => 2,
+            DtypeVariant::Long | DtypeVariant::ULong => 3,
+            DtypeVariant::LongLong | DtypeVariant::ULongLong => 4,
+            _ => 0,
+        }
+    }
+
+    fn to_unsigned(&self) -> Self {
+        match self {
+            DtypeVariant::Int => DtypeVariant::UInt,
+            DtypeVariant::Long => DtypeVariant::ULong,
+            DtypeVariant::LongLong => DtypeVariant::ULongLong,
+            _ => self.clone(),
+        }
+    }
+
+    /// Largest value of an integer type
+    pub fn max_value(&self) -> u64 {
+        match self {
+            DtypeVariant::Char => i8::MAX as u64,
+            DtypeVariant::UChar => u8::MAX as u64,
+            DtypeVariant::Int => i32::MAX as u64,
+            DtypeVariant::UInt => u32::MAX as u64,
+            DtypeVariant::Long | DtypeVariant::LongLong => i64::MAX as u64,
+            DtypeVariant::ULong | DtypeVariant::ULongLong => u64::MAX,
+            _ => 0,
         }
     }
 }
@@ -38,7 +133,15 @@ impl fmt::Display for DtypeVariant {
             "{}",
             match self {
                 DtypeVariant::Int => "int".to_string(),
+                DtypeVariant::UInt => "unsigned int".to_string(),
+                DtypeVariant::Long => "long".to_string(),
+                DtypeVariant::ULong => "unsigned long".to_string(),
+                DtypeVariant::LongLong => "long long".to_string(),
+                DtypeVariant::ULongLong => "unsigned long long".to_string(),
                 DtypeVariant::Char => "char".to_string(),
+                DtypeVariant::UChar => "unsigned char".to_string(),
+                DtypeVariant::Float => "float".to_string(),
+                DtypeVariant::Double => "double".to_string(),
                 DtypeVariant::Void => "void".to_string(),
                 DtypeVariant::Struct { name } => format!("struct {}", name),
             }
@@ -65,9 +168,9 @@ impl fmt::Display for Dtype {
 }
 
 impl Dtype {
-    pub fn new(dtype: &str) -> Result<Self, Error> {
+    pub fn new(tok: &Token) -> Result<Self, Error> {
         Ok(Self {
-            variant: DtypeVariant::new(dtype)?,
+            variant: DtypeVariant::new(tok)?,
             nderefs: 0,
         })
     }
@@ -90,8 +193,12 @@ impl Dtype {
                 8
             } else {
                 match &self.variant {
-                    DtypeVariant::Int => 4,
-                    DtypeVariant::Char => 1,
+                    DtypeVariant::Int | DtypeVariant::UInt => 4,
+                    DtypeVariant::Long | DtypeVariant::ULong => 8,
+                    DtypeVariant::LongLong | DtypeVariant::ULongLong => 8,
+                    DtypeVariant::Char | DtypeVariant::UChar => 1,
+                    DtypeVariant::Float => 4,
+                    DtypeVariant::Double => 8,
                     DtypeVariant::Void => 0,
                     DtypeVariant::Struct { name } => {
                         let NodeVariant::Struct { fields, .. } = scope.find_struct(name.as_str(), 0)?
@@ -128,7 +235,12 @@ impl Dtype {
         )
     }
 
+    /// Floating types use xmm0 for 'a', xmm1 for 'b' and so on
     pub fn register(&self, reg: char, scope: &Scope) -> Result<String, Error> {
+        if self.is_float() {
+            return Ok(format!("xmm{}", reg as u32 - 'a' as u32));
+        }
+
         Ok(match self.num_bytes(scope)? {
             1 => format!("{}l", reg),
             4 => format!("e{}x", reg),
@@ -141,12 +253,82 @@ impl Dtype {
         })
     }
 
-    pub fn default_node(&self, line: usize) -> Node {
+    /// Value of a definition without an initializer
+    pub fn default_node(&self, line: usize) -> Result<Node, Error> {
+        Ok(match self.variant {
+            _ if self.is_integer() => Node::new(
+                NodeVariant::Int {
+                    value: 0,
+                    dtype: self.variant.clone(),
+                },
+                line,
+            ),
+            _ if self.is_float() => Node::new(
+                NodeVariant::Float {
+                    value: 0.0,
+                    dtype: self.variant.clone(),
+                },
+                line,
+            ),
+            // Null pointer
+            _ if self.nderefs > 0 => Node::new(
+                NodeVariant::Cast {
+                    dtype: self.clone(),
+                    value: Node::new(NodeVariant::Int { value: 0, dtype: DtypeVariant::ULong }, line),
+                },
+                line,
+            ),
+            _ => return Err(Error::new(ErrorType::NoDefaultValue(self.clone()), line)),
+        })
+    }
+
+    /// Integers, not pointers to them
+    pub fn is_integer(&self) -> bool {
+        self.nderefs == 0 && self.variant.is_integer()
+    }
+
+    /// float and double, not pointers to them
+    pub fn is_float(&self) -> bool {
+        self.nderefs == 0 && self.variant.is_float()
+    }
+
+    pub fn is_arithmetic(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    /// Integer promotion (C11 6.3.1.1), types smaller than int become int
+    pub fn promoted(&self) -> Dtype {
         match self.variant {
-            DtypeVariant::Int => Node::new(NodeVariant::Int { value: 0 }, line),
-            _ => todo!(),
+            DtypeVariant::Char | DtypeVariant::UChar if self.nderefs == 0 => Dtype::from_fields(DtypeVariant::Int),
+            _ => self.clone(),
         }
     }
+
+    /// Type both operands of an arithmetic operator are converted to (C11 6.3.1.8)
+    pub fn common(a: &Dtype, b: &Dtype) -> Dtype {
+        let (a, b): (DtypeVariant, DtypeVariant) = (a.promoted().variant, b.promoted().variant);
+
+        let variant: DtypeVariant = if a == b {
+            a
+        } else if a == DtypeVariant::Double || b == DtypeVariant::Double {
+            DtypeVariant::Double
+        } else if a == DtypeVariant::Float || b == DtypeVariant::Float {
+            DtypeVariant::Float
+        } else if a.is_unsigned() == b.is_unsigned() {
+            if a.rank() > b.rank() { a } else { b }
+        } else {
+            let (unsigned, signed) = if a.is_unsigned() { (a, b) } else { (b, a) };
+            if unsigned.rank() >= signed.rank() {
+                unsigned
+            } else if signed.max_value() >= unsigned.max_value() {
+                signed
+            } else {
+                signed.to_unsigned()
+            }
+        };
+
+        Dtype::from_fields(variant)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -155,15 +337,22 @@ pub enum NodeVariant {
     Cpd {
         values: Vec<Node>,
     },
+    /// One char per byte
     Str {
         value: String,
     },
     Int {
-        value: i32,
+        value: u64,
+        dtype: DtypeVariant,
     },
     Char {
         value: char,
     },
+    /// float and double literals, float ones are already rounded
+    Float {
+        value: f64,
+        dtype: DtypeVariant,
+    },
     Fcall {
         name: String,
         args: Vec<Node>,
@@ -185,6 +374,8 @@ pub enum NodeVariant {
     If {
         cond: Node,
         body: Node,
+        /// Noop without an else
+        else_body: Node,
     },
     Return {
         value: Node,
@@ -198,6 +389,17 @@ pub enum NodeVariant {
         utype: TokenType,
         r: Node,
     },
+    /// cond ? l : r, only the selected arm is evaluated
+    Ternary {
+        cond: Node,
+        l: Node,
+        r: Node,
+    },
+    /// l++ and l--, evaluating to the value before
+    Postfix {
+        ptype: TokenType,
+        l: Node,
+    },
     Struct {
         name: String,
         /// Only vardefs
@@ -213,16 +415,48 @@ pub enum NodeVariant {
         cond: Node,
         body: Node,
     },
+    /// do body while (cond), the body runs before the first check
+    DoWhile {
+        body: Node,
+        cond: Node,
+    },
+    /// Innermost loop or switch
+    Break,
+    /// Innermost loop
+    Continue,
+    Goto {
+        name: String,
+    },
+    /// name:, scoped to the function
+    Label {
+        name: String,
+    },
+    Switch {
+        cond: Node,
+        body: Node,
+    },
+    /// case value:, or default: with a Noop value.
+    /// Labels are statements of their own, the statements after them follow in the block.
+    Case {
+        value: Node,
+    },
     InitList {
         dtype: Dtype,
         fields: Vec<(String, Node)>,
     },
+    /// Conversion between arithmetic types, or 0 to a null pointer
+    Cast {
+        dtype: Dtype,
+        value: Node,
+    },
 }
 
 #[derive(Clone, Debug)]
 pub struct Node {
     pub variant: Box<NodeVariant>,
     pub line: usize,
+    /// Source range, empty for nodes made up by the compiler
+    pub span: Span,
 }
 
 impl Node {
@@ -230,21 +464,32 @@ impl Node {
         Self {
             variant: Box::new(variant),
             line,
+            span: Span::default(),
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        if !span.is_empty() {
+            self.line = span.line;
         }
+        self.span = span;
+        self
     }
 
     pub fn dtype(&self, scope: &Scope) -> Result<Dtype, Error> {
         Ok(match self.variant.as_ref() {
             NodeVariant::Str { .. } => Dtype::from_fields_nderefs(DtypeVariant::Char, 1),
-            NodeVariant::Int { .. } => Dtype::from_fields(DtypeVariant::Int),
+            NodeVariant::Int { dtype, .. } => Dtype::from_fields(dtype.clone()),
             NodeVariant::Char { .. } => Dtype::from_fields(DtypeVariant::Char),
+            NodeVariant::Float { dtype, .. } => Dtype::from_fields(dtype.clone()),
             NodeVariant::Fcall { name, .. } => {
-                scope.find_fdef(name, self.line)?.node.dtype(scope)?
+                scope.find_fdef(name, self.line).map_err(|e| e.or_span(self.span))?.node.dtype(scope)?
             }
             NodeVariant::Fdef { rtype, .. } => rtype.clone(),
             NodeVariant::Vardef { dtype, .. } => dtype.clone(),
-            NodeVariant::Var { name } => scope.find_vardef(name, self.line)?.node.dtype(scope)?,
+            NodeVariant::Var { name } => scope.find_vardef(name, self.line).map_err(|e| e.or_span(self.span))?.node.dtype(scope)?,
             NodeVariant::InitList { dtype, .. } => dtype.clone(),
+            NodeVariant::Cast { dtype, .. } => dtype.clone(),
             NodeVariant::Binop {
                 l,
                 r,
@@ -298,6 +543,54 @@ impl Node {
                 let field: &Node = field_from_struct(sdef, r)?;
                 field.dtype(scope)?
             }
+            NodeVariant::Binop {
+                btype:
+                    TokenType::EqualCmp
+                    | TokenType::NotEqual
+                    | TokenType::Less
+                    | TokenType::Greater
+                    | TokenType::LessEqual
+                    | TokenType::GreaterEqual
+                    | TokenType::And
+                    | TokenType::Or,
+                ..
+            } => Dtype::from_fields(DtypeVariant::Int),
+            NodeVariant::Binop { l, r, btype } if btype.is_bitwise() => {
+                let (ldtype, rdtype): (Dtype, Dtype) = (l.dtype(scope)?, r.dtype(scope)?);
+                if !ldtype.is_integer() || !rdtype.is_integer() {
+                    return Err(Error::new(ErrorType::InvalidOperands(*btype, ldtype, rdtype), self.line)
+                        .or_span(self.span));
+                }
+
+                // Shifts have the type of their promoted left operand
+                match btype {
+                    TokenType::Shl | TokenType::Shr => ldtype.promoted(),
+                    _ => Dtype::common(&ldtype, &rdtype),
+                }
+            }
+            NodeVariant::Binop {
+                l,
+                r,
+                btype:
+                    btype @ (TokenType::Plus
+                    | TokenType::Minus
+                    | TokenType::Star
+                    | TokenType::Div
+                    | TokenType::Percent),
+            } => {
+                let (ldtype, rdtype): (Dtype, Dtype) = (l.dtype(scope)?, r.dtype(scope)?);
+                let float: bool = ldtype.is_float() || rdtype.is_float();
+                if ldtype.is_arithmetic() && rdtype.is_arithmetic() && !(float && *btype == TokenType::Percent) {
+                    Dtype::common(&ldtype, &rdtype)
+                } else if float || matches!(btype, TokenType::Star | TokenType::Div | TokenType::Percent) {
+                    // Only + and - take pointers, with an integer
+                    return Err(Error::new(ErrorType::InvalidOperands(*btype, ldtype, rdtype), self.line)
+                        .or_span(self.span));
+                } else {
+                    ldtype
+                }
+            }
+            NodeVariant::Binop { r, btype: TokenType::Comma, .. } => r.dtype(scope)?,
             NodeVariant::Binop { l, .. } => l.dtype(scope)?,
             NodeVariant::Unop { utype: TokenType::Amp, r } => {
                 let mut dtype: Dtype = r.dtype(scope)?;
@@ -309,11 +602,173 @@ impl Node {
                 dtype.nderefs -= 1;
                 dtype
             }
+            NodeVariant::Unop { utype: TokenType::Not, .. } => Dtype::from_fields(DtypeVariant::Int),
+            NodeVariant::Unop { utype: TokenType::Tilde, r } => {
+                let dtype: Dtype = r.dtype(scope)?;
+                if !dtype.is_integer() {
+                    return Err(Error::new(ErrorType::InvalidOperand(TokenType::Tilde, dtype), self.line)
+                        .or_span(self.span));
+                }
+                dtype.promoted()
+            }
+            NodeVariant::Unop { utype: utype @ (TokenType::Minus | TokenType::Plus), r } => {
+                let dtype: Dtype = r.dtype(scope)?;
+                if !dtype.is_arithmetic() {
+                    return Err(Error::new(ErrorType::InvalidOperand(*utype, dtype), self.line)
+                        .or_span(self.span));
+                }
+                dtype.promoted()
+            }
             NodeVariant::Unop { r, .. } => r.dtype(scope)?,
+            NodeVariant::Postfix { l, .. } => l.dtype(scope)?,
+            NodeVariant::Ternary { l, r, .. } => {
+                let (ldtype, rdtype): (Dtype, Dtype) = (l.dtype(scope)?, r.dtype(scope)?);
+                // 0 converts to any pointer
+                let is_null = |n: &Node| matches!(n.variant.as_ref(), NodeVariant::Int { value: 0, .. });
+                if ldtype.is_arithmetic() && rdtype.is_arithmetic() {
+                    Dtype::common(&ldtype, &rdtype)
+                } else if ldtype == rdtype || (ldtype.nderefs > 0 && is_null(r)) {
+                    ldtype
+                } else if rdtype.nderefs > 0 && is_null(l) {
+                    rdtype
+                } else {
+                    return Err(Error::new(ErrorType::TernaryTypeMismatch(ldtype, rdtype), self.line)
+                        .or_span(l.span.to(r.span)));
+                }
+            }
             _ => panic!("{:?} doesn't have a dtype.", self.variant),
         })
     }
 
+    /// Implicit conversion to an arithmetic type.
+    /// Literals are converted in place, everything else is wrapped in a cast.
+    pub fn cast(&self, dtype: &Dtype, scope: &Scope) -> Result<Node, Error> {
+        if self.dtype(scope)? == *dtype || !dtype.is_arithmetic() {
+            return Ok(self.clone());
+        }
+
+        // Value of a literal as a float, and as integer bits
+        let literal: Option<(f64, u64)> = match self.variant.as_ref() {
+            NodeVariant::Int { value, dtype } if dtype.is_unsigned() => Some((*value as f64, *value)),
+            NodeVariant::Int { value, .. } => Some((*value as i64 as f64, *value)),
+            // Plain char is signed
+            NodeVariant::Char { value } => Some((*value as u8 as i8 as f64, *value as u8 as i8 as u64)),
+            // Floats truncate toward zero
+            NodeVariant::Float { value, .. } if dtype.variant.is_unsigned() => Some((*value, *value as u64)),
+            NodeVariant::Float { value, .. } => Some((*value, *value as i64 as u64)),
+            _ => None,
+        };
+
+        Ok(match literal {
+            Some((value, _)) if dtype.is_float() => Node::new(
+                NodeVariant::Float {
+                    value: if dtype.variant == DtypeVariant::Float { value as f32 as f64 } else { value },
+                    dtype: dtype.variant.clone(),
+                },
+                self.line,
+            )
+            .with_span(self.span),
+            Some((_, value)) => {
+                // Keep the low bits, written as the value they have in the new type
+                let bits: u32 = dtype.num_bytes(scope)? as u32 * 8;
+                let mut value: u64 = if bits == 64 { value } else { value & ((1 << bits) - 1) };
+                if !dtype.variant.is_unsigned() && value > dtype.variant.max_value() {
+                    value |= !0 << (bits - 1);
+                }
+
+                Node::new(
+                    NodeVariant::Int {
+                        value,
+                        dtype: dtype.variant.clone(),
+                    },
+                    self.line,
+                )
+                .with_span(self.span)
+            }
+            None => Node::new(
+                NodeVariant::Cast {
+                    dtype: dtype.clone(),
+                    value: self.clone(),
+                },
+                self.line,
+            )
+            .with_span(self.span),
+        })
+    }
+
+    /// -x for integer and float literals, so negative constants are literals.
+    /// The type doesn't change, -2147483648 is a long like 2147483648.
+    pub fn negate_literal(&self) -> Option<Node> {
+        let variant: NodeVariant = match self.variant.as_ref() {
+            NodeVariant::Int { value, dtype } if dtype.is_unsigned() => NodeVariant::Int {
+                value: value.wrapping_neg() & dtype.max_value(),
+                dtype: dtype.clone(),
+            },
+            NodeVariant::Int { value, dtype } => NodeVariant::Int {
+                value: value.wrapping_neg(),
+                dtype: dtype.clone(),
+            },
+            NodeVariant::Float { value, dtype } => NodeVariant::Float {
+                value: -value,
+                dtype: dtype.clone(),
+            },
+            _ => return None,
+        };
+        Some(Node::new(variant, self.line))
+    }
+
+    /// Value of an integer constant expression, evaluated in 64 bits.
+    /// None if it isn't one, or it divides by zero.
+    pub fn int_const(&self) -> Option<i64> {
+        Some(match self.variant.as_ref() {
+            NodeVariant::Int { value, .. } => *value as i64,
+            NodeVariant::Char { value } => *value as u8 as i8 as i64,
+            NodeVariant::Cast { value, .. } => value.int_const()?,
+            NodeVariant::Unop { utype, r } => {
+                let r: i64 = r.int_const()?;
+                match utype {
+                    TokenType::Minus => r.wrapping_neg(),
+                    TokenType::Plus => r,
+                    TokenType::Tilde => !r,
+                    TokenType::Not => (r == 0) as i64,
+                    _ => return None,
+                }
+            }
+            NodeVariant::Binop { btype, l, r } => {
+                let (l, r): (i64, i64) = (l.int_const()?, r.int_const()?);
+                match btype {
+                    TokenType::Plus => l.wrapping_add(r),
+                    TokenType::Minus => l.wrapping_sub(r),
+                    TokenType::Star => l.wrapping_mul(r),
+                    TokenType::Div => l.checked_div(r)?,
+                    TokenType::Percent => l.checked_rem(r)?,
+                    TokenType::Amp => l & r,
+                    TokenType::Pipe => l | r,
+                    TokenType::Caret => l ^ r,
+                    TokenType::Shl => l.wrapping_shl(r as u32),
+                    TokenType::Shr => l.wrapping_shr(r as u32),
+                    TokenType::Less => (l < r) as i64,
+                    TokenType::Greater => (l > r) as i64,
+                    TokenType::LessEqual => (l <= r) as i64,
+                    TokenType::GreaterEqual => (l >= r) as i64,
+                    TokenType::EqualCmp => (l == r) as i64,
+                    TokenType::NotEqual => (l != r) as i64,
+                    TokenType::And => (l != 0 && r != 0) as i64,
+                    TokenType::Or => (l != 0 || r != 0) as i64,
+                    _ => return None,
+                }
+            }
+            NodeVariant::Ternary { cond, l, r } => {
+                if cond.int_const()? != 0 {
+                    l.int_const()?
+                } else {
+                    r.int_const()?
+                }
+            }
+            _ => return None,
+        })
+    }
+
     /// For var / vardef, everything else will be returned as is.
     pub fn strip<'a>(&'a self, scope: &'a Scope) -> Result<&'a Node, Error> {
         Ok(match self.variant.as_ref() {
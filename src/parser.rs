@@ -1,38 +1,33 @@
 use crate::error::{Error, ErrorType};
 use crate::lexer::*;
+use crate::literal::{self, LiteralError};
 use crate::node::*;
+use crate::span::Span;
 
-#[derive(Clone)]
 pub struct Parser {
-    lexer: Lexer,
-    curr: Token,
-    prev: Token,
-    prev_expr: Node,
+    tokens: TokenStream,
 }
 
 impl Parser {
     pub fn new(contents: &str) -> Result<Self, Error> {
-        let mut lexer: Lexer = Lexer::new(contents);
-        let curr = lexer.next()?;
         Ok(Self {
-            lexer,
-            curr: curr.clone(),
-            prev: curr,
-            prev_expr: Node::new(NodeVariant::Noop, 0),
+            tokens: TokenStream::new(contents)?,
         })
     }
 
     pub fn parse(&mut self) -> Result<Node, Error> {
-        let line: usize = self.curr.line;
+        let line: usize = self.curr().line;
+        let start: Span = self.curr().span;
         let mut cpd_values: Vec<Node> = Vec::new();
 
         loop {
-            cpd_values.push(match self.parse_expr(false)? {
+            cpd_values.push(match self.parse_stmt()? {
                 Some(x) => x,
                 None => break,
             });
 
-            if self.prev.ttype != TokenType::Rbrace && self.prev.ttype != TokenType::Semi {
+            // Labels end with ':'
+            if !matches!(self.prev().ttype, TokenType::Rbrace | TokenType::Semi | TokenType::Colon) {
                 self.expect(TokenType::Semi)?;
             }
         }
@@ -41,32 +36,67 @@ impl Parser {
             cpd_values.push(Node::new(NodeVariant::Noop, line));
         }
 
-        Ok(Node::new(NodeVariant::Cpd { values: cpd_values }, line))
+        let span: Span = if self.prev().span.start < start.start {
+            // Empty
+            Span::default()
+        } else {
+            self.span_from(start)
+        };
+        Ok(Node::new(NodeVariant::Cpd { values: cpd_values }, line).with_span(span))
     }
 
     fn expect(&mut self, ttype: TokenType) -> Result<(), Error> {
-        if self.curr.ttype == ttype {
-            self.prev = self.curr.clone();
-            self.curr = self.lexer.next()?;
+        if self.curr().ttype == ttype {
+            self.tokens.advance();
             Ok(())
         } else {
             Err(Error::new(
-                ErrorType::UnexpectedToken(self.curr.ttype, ttype),
-                self.curr.line,
-            ))
+                ErrorType::UnexpectedToken(self.curr().ttype, ttype),
+                self.curr().line,
+            )
+            .with_span(self.curr().span))
         }
     }
 
+    fn curr(&self) -> &Token {
+        self.tokens.curr()
+    }
+
+    fn prev(&self) -> &Token {
+        self.tokens.prev()
+    }
+
+    /// Name of an identifier, keywords can't be used as one
+    fn expect_id(&mut self) -> Result<String, Error> {
+        if let TokenType::Keyword(_) = self.curr().ttype {
+            return Err(Error::new(
+                ErrorType::KeywordAsIdentifier(self.curr().value.as_str()),
+                self.curr().line,
+            )
+            .with_span(self.curr().span));
+        }
+
+        self.expect(TokenType::Id)?;
+        Ok(self.prev().value.clone())
+    }
+
+    /// From start to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.prev().span)
+    }
+
     fn parse_expr(&mut self, only_one: bool) -> Result<Option<Node>, Error> {
-        while self.curr.ttype == TokenType::Semi {
+        while self.curr().ttype == TokenType::Semi {
             self.expect(TokenType::Semi)?;
         }
 
-        let mut n: Option<Node> = match self.curr.ttype {
+        let mut n: Option<Node> = match self.curr().ttype {
             TokenType::Str => Some(self.parse_str()?),
             TokenType::Int => Some(self.parse_int()?),
+            TokenType::Float => Some(self.parse_float()?),
             TokenType::Char => Some(self.parse_char()?),
             TokenType::Id => Some(self.parse_id()?),
+            TokenType::Keyword(keyword) => Some(self.parse_keyword(keyword)?),
             TokenType::Lbrace => {
                 self.expect(TokenType::Lbrace)?;
                 let node = self.parse()?;
@@ -75,21 +105,20 @@ impl Parser {
                 Some(node)
             }
             TokenType::Lparen => {
-                let mut copy: Parser = self.clone();
-                copy.expect(TokenType::Lparen)?;
-                let dtype: Result<Dtype, Error> = copy.parse_dtype();
-                if dtype.is_ok()
-                    && copy.curr.ttype == TokenType::Rparen
-                    && copy
-                        .lexer
-                        .peek(1)
-                        .is_ok_and(|x| x.ttype == TokenType::Lbrace)
-                {
+                // Try to parse the start of an init list, "(dtype) {"
+                let checkpoint: usize = self.tokens.checkpoint();
+                self.expect(TokenType::Lparen)?;
+                let is_init_list: bool = self.parse_dtype().is_ok()
+                    && self.curr().ttype == TokenType::Rparen
+                    && self.tokens.peek(1).ttype == TokenType::Lbrace;
+                self.tokens.rewind(checkpoint);
+
+                if is_init_list {
                     Some(self.parse_init_list()?)
                 } else {
                     self.expect(TokenType::Lparen)?;
 
-                    let expr = self.parse_expr(false)?.unwrap();
+                    let expr = self.parse_comma_expr()?.unwrap();
                     self.expect(TokenType::Rparen)?;
 
                     Some(expr)
@@ -98,96 +127,215 @@ impl Parser {
             _ => None,
         };
 
+        if let Some(x) = n {
+            n = Some(self.parse_postfix(x)?);
+        }
+
         // Part of match, but can't put if in match
-        if n.is_none() && self.curr.ttype.is_unop() {
+        if n.is_none() && self.curr().ttype.is_unop() {
             n = Some(self.parse_unop()?);
         }
 
-        if let Some(n) = &n {
-            self.prev_expr = n.clone();
+        match n {
+            Some(n) if !only_one => Ok(Some(self.parse_binop(n, 0)?)),
+            _ => Ok(n),
         }
+    }
 
-        if !only_one && self.curr.ttype.is_binop() {
-            return Ok(Some(self.parse_binop()?));
+    /// A statement in a block or the body of if, a loop or switch.
+    /// A lone ';' is an empty statement, so it isn't skipped like in parse_expr.
+    fn parse_stmt(&mut self) -> Result<Option<Node>, Error> {
+        if self.curr().ttype == TokenType::Semi {
+            let line: usize = self.curr().line;
+            self.expect(TokenType::Semi)?;
+            return Ok(Some(Node::new(NodeVariant::Noop, line)));
+        }
+
+        // Labels are only statements, an identifier and ':' is part of ?: elsewhere
+        if self.curr().ttype == TokenType::Id && self.tokens.peek(1).ttype == TokenType::Colon {
+            return Ok(Some(self.parse_label()?));
+        }
+
+        self.parse_comma_expr()
+    }
+
+    /// Expression including comma operators, where commas don't separate a list
+    fn parse_comma_expr(&mut self) -> Result<Option<Node>, Error> {
+        let Some(mut n) = self.parse_expr(false)? else { return Ok(None) };
+        while self.curr().ttype == TokenType::Comma {
+            let line: usize = self.curr().line;
+            self.expect(TokenType::Comma)?;
+            let r: Node = self.parse_expr(false)?.unwrap();
+
+            let span: Span = n.span.to(r.span);
+            n = Node::new(NodeVariant::Binop { btype: TokenType::Comma, l: n, r }, line).with_span(span);
+        }
+
+        Ok(Some(n))
+    }
+
+    /// Member accesses and increments after an operand, which bind tighter than any other operator
+    fn parse_postfix(&mut self, mut n: Node) -> Result<Node, Error> {
+        while self.curr().ttype.is_postfix() {
+            let line: usize = self.curr().line;
+            let ttype: TokenType = self.curr().ttype;
+            self.expect(ttype)?;
+
+            n = match ttype {
+                TokenType::Dot | TokenType::Arrow => {
+                    // Member names are identifiers
+                    let r: Node = self.parse_var()?;
+                    let span: Span = n.span.to(r.span);
+                    Node::new(NodeVariant::Binop { btype: ttype, l: n, r }, line).with_span(span)
+                }
+                _ => {
+                    let span: Span = self.span_from(n.span);
+                    Node::new(NodeVariant::Postfix { ptype: ttype, l: n }, line).with_span(span)
+                }
+            };
         }
 
         Ok(n)
     }
 
     fn parse_int(&mut self) -> Result<Node, Error> {
-        // Lexer guarantees this is a valid integer, it wouldn't be accepted otherwise
-        let int_value: i32 = self.curr.value.parse::<i32>().unwrap();
+        let start: Span = self.curr().span;
+        let (value, dtype) = literal::parse_int(&self.curr().value).map_err(|e| {
+            let literal: &str = self.curr().value.as_str();
+            Error::new(
+                match e {
+                    LiteralError::Invalid => ErrorType::InvalidIntegerLiteral(literal),
+                    LiteralError::TooLarge => ErrorType::IntegerLiteralTooLarge(literal),
+                },
+                self.curr().line,
+            )
+            .with_span(start)
+        })?;
         self.expect(TokenType::Int)?;
-        Ok(Node::new(
-            NodeVariant::Int { value: int_value },
-            self.curr.line,
-        ))
+        Ok(Node::new(NodeVariant::Int { value, dtype }, self.curr().line).with_span(start))
+    }
+
+    fn parse_float(&mut self) -> Result<Node, Error> {
+        let start: Span = self.curr().span;
+        let (value, dtype) = literal::parse_float(&self.curr().value).map_err(|e| {
+            let literal: &str = self.curr().value.as_str();
+            Error::new(
+                match e {
+                    LiteralError::Invalid => ErrorType::InvalidFloatLiteral(literal),
+                    LiteralError::TooLarge => ErrorType::FloatLiteralTooLarge(literal),
+                },
+                self.curr().line,
+            )
+            .with_span(start)
+        })?;
+        self.expect(TokenType::Float)?;
+        Ok(Node::new(NodeVariant::Float { value, dtype }, self.curr().line).with_span(start))
     }
 
     fn parse_str(&mut self) -> Result<Node, Error> {
-        self.expect(TokenType::Str)?;
-        Ok(Node::new(
-            NodeVariant::Str {
-                value: self.prev.value.clone(),
-            },
-            self.curr.line,
-        ))
+        // Adjacent literals are concatenated
+        let start: Span = self.curr().span;
+        let mut value: String = String::new();
+        while self.curr().ttype == TokenType::Str {
+            value.push_str(self.curr().value.as_str());
+            self.expect(TokenType::Str)?;
+        }
+
+        Ok(Node::new(NodeVariant::Str { value }, self.curr().line).with_span(self.span_from(start)))
     }
 
     fn parse_char(&mut self) -> Result<Node, Error> {
         self.expect(TokenType::Char)?;
         Ok(Node::new(
             NodeVariant::Char {
-                value: self.prev.value.clone().chars().next().unwrap(),
+                value: self.prev().value.clone().chars().next().unwrap(),
             },
-            self.curr.line,
-        ))
+            self.curr().line,
+        )
+        .with_span(self.prev().span))
     }
 
     fn parse_dtype(&mut self) -> Result<Dtype, Error> {
-        let mut dtype: Dtype = Dtype::new(&self.curr.value)?;
-        self.expect(TokenType::Id)?;
+        let mut dtype: Dtype = Dtype::new(self.curr())?;
+        if dtype.is_arithmetic() {
+            // Arithmetic types can take several specifiers, like "unsigned long long int"
+            let line: usize = self.curr().line;
+            let start: Span = self.curr().span;
+            let checkpoint: usize = self.tokens.checkpoint();
+            let mut specifiers: Vec<Keyword> = Vec::new();
+            while let TokenType::Keyword(keyword) = self.curr().ttype {
+                if !keyword.is_arithmetic_specifier() {
+                    break;
+                }
+                specifiers.push(keyword);
+                self.expect(TokenType::Keyword(keyword))?;
+            }
+
+            let Some(variant) = DtypeVariant::from_specifiers(&specifiers) else {
+                let span: Span = self.span_from(start);
+                // Spelled as written, for the message
+                self.tokens.rewind(checkpoint);
+                let words: Vec<String> = (0..specifiers.len()).map(|i| self.tokens.peek(i).value.clone()).collect();
+                return Err(Error::new(ErrorType::InvalidDtypeFromStr(words.join(" ").as_str()), line).with_span(span));
+            };
+            dtype.variant = variant;
+        } else {
+            self.expect(self.curr().ttype)?;
+        }
+
         if let DtypeVariant::Struct { name } = &mut dtype.variant {
-            *name = self.curr.value.clone();
-            self.expect(TokenType::Id)?;
+            *name = self.expect_id()?;
         }
 
-        while self.curr.ttype == TokenType::Star {
+        while self.curr().ttype == TokenType::Star {
             dtype.nderefs += 1;
-            self.expect(self.curr.ttype)?;
+            self.expect(self.curr().ttype)?;
         }
         Ok(dtype)
     }
 
     fn parse_id(&mut self) -> Result<Node, Error> {
-        match self.curr.value.as_str() {
-            "if" => self.parse_if(),
-            "return" => self.parse_return(),
-            "struct" => self.parse_struct(),
-            "for" => self.parse_for(),
-            "while" => self.parse_while(),
-            _ => match self.lexer.peek(1)?.ttype {
-                TokenType::Lparen => self.parse_fcall(),
-                _ => self.parse_var(),
-            },
+        match self.tokens.peek(1).ttype {
+            TokenType::Lparen => self.parse_fcall(),
+            _ => self.parse_var(),
+        }
+    }
+
+    /// Statements and definitions starting with a keyword
+    fn parse_keyword(&mut self, keyword: Keyword) -> Result<Node, Error> {
+        match keyword {
+            Keyword::If => self.parse_if(),
+            Keyword::Return => self.parse_return(),
+            Keyword::Struct => self.parse_struct(),
+            Keyword::For => self.parse_for(),
+            Keyword::While => self.parse_while(),
+            Keyword::Do => self.parse_do_while(),
+            Keyword::Switch => self.parse_switch(),
+            Keyword::Break | Keyword::Continue | Keyword::Goto => self.parse_jump(keyword),
+            Keyword::Case | Keyword::Default => self.parse_case(keyword),
+            _ if keyword.is_dtype() => self.parse_vardef(),
+            Keyword::Else => Err(Error::new(ErrorType::ElseWithoutIf, self.curr().line).with_span(self.curr().span)),
+            _ => Err(Error::new(
+                ErrorType::UnsupportedKeyword(self.curr().value.as_str()),
+                self.curr().line,
+            )
+            .with_span(self.curr().span)),
         }
     }
 
     fn parse_fcall(&mut self) -> Result<Node, Error> {
-        let name: String = self.curr.value.clone();
+        let start: Span = self.curr().span;
+        let name: String = self.curr().value.clone();
         self.expect(TokenType::Id)?;
 
         let mut args: Vec<Node> = Vec::new();
-        let line: usize = self.curr.line;
+        let line: usize = self.curr().line;
 
         self.expect(TokenType::Lparen)?;
-        loop {
-            match self.parse_expr(false)? {
-                Some(expr) => args.push(expr),
-                None => break,
-            };
+        while let Some(expr) = self.parse_expr(false)? {
+            args.push(expr);
 
-            if self.curr.ttype != TokenType::Rparen {
+            if self.curr().ttype != TokenType::Rparen {
                 self.expect(TokenType::Comma)?;
             } else {
                 break;
@@ -195,28 +343,28 @@ impl Parser {
         }
         self.expect(TokenType::Rparen)?;
 
-        Ok(Node::new(NodeVariant::Fcall { name, args }, line))
+        Ok(Node::new(NodeVariant::Fcall { name, args }, line).with_span(self.span_from(start)))
     }
 
     fn parse_fdef(&mut self, rtype: Dtype) -> Result<Node, Error> {
-        let line: usize = self.curr.line;
-        let name: String = self.prev.value.clone();
+        let line: usize = self.curr().line;
+        let start: Span = self.prev().span;
+        let name: String = self.prev().value.clone();
         let mut params: Vec<Node> = Vec::new();
 
         self.expect(TokenType::Lparen)?;
-        loop {
-            match self.parse_expr(false)? {
-                Some(expr) => params.push(expr),
-                None => break,
-            };
+        while let Some(expr) = self.parse_expr(false)? {
+            params.push(expr);
 
-            if self.curr.ttype != TokenType::Rparen {
+            if self.curr().ttype != TokenType::Rparen {
                 self.expect(TokenType::Comma)?;
             }
         }
         self.expect(TokenType::Rparen)?;
+        // Up to the closing paren, bodies are too long to point at
+        let span: Span = self.span_from(start);
 
-        let body: Node = if self.curr.ttype == TokenType::Semi {
+        let body: Node = if self.curr().ttype == TokenType::Semi {
             Node::new(NodeVariant::Noop, 0)
         } else {
             self.expect(TokenType::Lbrace)?;
@@ -233,137 +381,139 @@ impl Parser {
                 rtype,
             },
             line,
-        ))
+        )
+        .with_span(span))
     }
 
     fn parse_return(&mut self) -> Result<Node, Error> {
-        self.expect(TokenType::Id)?;
-        Ok(Node::new(
-            NodeVariant::Return {
-                value: self.parse_expr(false)?.unwrap(),
-            },
-            self.curr.line,
-        ))
+        let start: Span = self.curr().span;
+        self.expect(TokenType::Keyword(Keyword::Return))?;
+        let value: Node = self.parse_comma_expr()?.unwrap();
+        Ok(Node::new(NodeVariant::Return { value }, self.curr().line).with_span(self.span_from(start)))
     }
 
     fn parse_var(&mut self) -> Result<Node, Error> {
-        // Start on name
-        let name: String = self.curr.value.clone();
-
-        if Dtype::new(&name).is_ok() {
-            self.parse_vardef()
-        } else {
-            self.expect(TokenType::Id)?;
-            Ok(Node::new(NodeVariant::Var { name }, self.curr.line))
-        }
+        let name: String = self.expect_id()?;
+        Ok(Node::new(NodeVariant::Var { name }, self.curr().line).with_span(self.prev().span))
     }
 
     fn parse_unop(&mut self) -> Result<Node, Error> {
-        self.expect(self.curr.ttype)?;
-        Ok(Node::new(
-            NodeVariant::Unop {
-                utype: self.prev.ttype,
-                r: self.parse_expr(true)?.unwrap(),
-            },
-            self.curr.line,
-        ))
+        let start: Span = self.curr().span;
+        self.expect(self.curr().ttype)?;
+        let utype: TokenType = self.prev().ttype;
+        let r: Node = self.parse_expr(true)?.unwrap();
+        let span: Span = self.span_from(start);
+        if utype == TokenType::Minus {
+            if let Some(n) = r.negate_literal() {
+                return Ok(n.with_span(span));
+            }
+        }
+        Ok(Node::new(NodeVariant::Unop { utype, r }, self.curr().line).with_span(span))
     }
 
     fn parse_vardef(&mut self) -> Result<Node, Error> {
+        let start: Span = self.curr().span;
         let dtype: Dtype = self.parse_dtype()?;
 
         let var: Node = self.parse_var()?;
-        let line: usize = self.curr.line;
+        let line: usize = self.curr().line;
 
-        match self.curr.ttype {
+        match self.curr().ttype {
             TokenType::Equal => {
                 self.expect(TokenType::Equal)?;
-                Ok(Node::new(
-                    NodeVariant::Vardef {
-                        var: var.clone(),
-                        value: match self.parse_expr(false)? {
-                            Some(x) => x,
-                            None => {
-                                return Err(Error::new(
-                                    ErrorType::VardefNoExpression(var.var_name().as_str()),
-                                    line,
-                                ))
-                            }
-                        },
-                        dtype,
-                    },
-                    line,
-                ))
+                let Some(value) = self.parse_expr(false)? else {
+                    return Err(Error::new(
+                        ErrorType::VardefNoExpression(var.var_name().as_str()),
+                        line,
+                    )
+                    .with_span(self.span_from(start)));
+                };
+                Ok(Node::new(NodeVariant::Vardef { var, value, dtype }, line)
+                    .with_span(self.span_from(start)))
             }
             TokenType::Lparen => self.parse_fdef(dtype),
             _ => Ok(Node::new(
                 NodeVariant::Vardef {
                     var,
-                    value: dtype.default_node(line),
+                    value: dtype.default_node(line).map_err(|e| e.with_span(self.span_from(start)))?,
                     dtype,
                 },
                 line,
-            )),
+            )
+            .with_span(self.span_from(start))),
         }
     }
 
     fn parse_if(&mut self) -> Result<Node, Error> {
-        let line: usize = self.curr.line;
-        self.expect(TokenType::Id)?;
+        let line: usize = self.curr().line;
+        let start: Span = self.curr().span;
+        self.expect(TokenType::Keyword(Keyword::If))?;
 
         self.expect(TokenType::Lparen)?;
-        let cond: Node = self.parse_expr(false)?.unwrap();
+        let cond: Node = self.parse_comma_expr()?.unwrap();
         self.expect(TokenType::Rparen)?;
+        let span: Span = self.span_from(start);
+        let body: Node = self.parse_stmt()?.unwrap();
 
-        Ok(Node::new(
-            NodeVariant::If {
-                cond,
-                body: self.parse_expr(false)?.unwrap(),
-            },
-            line,
-        ))
-    }
+        // The statement's ';' comes before else.
+        // A nested if takes the else first, so it goes with the closest if.
+        if self.curr().ttype == TokenType::Semi && self.tokens.peek(1).ttype == TokenType::Keyword(Keyword::Else) {
+            self.expect(TokenType::Semi)?;
+        }
+        let else_body: Node = if self.curr().ttype == TokenType::Keyword(Keyword::Else) {
+            self.expect(TokenType::Keyword(Keyword::Else))?;
+            self.parse_stmt()?.unwrap()
+        } else {
+            Node::new(NodeVariant::Noop, self.curr().line)
+        };
 
-    fn parse_binop(&mut self) -> Result<Node, Error> {
-        let line: usize = self.curr.line;
-        let btype: TokenType = self.curr.ttype;
-        self.expect(btype)?;
+        Ok(Node::new(NodeVariant::If { cond, body, else_body }, line).with_span(span))
+    }
 
-        let l: Node = self.prev_expr.clone();
-        // Only parse expression between operators
-        let r: Node = self.parse_expr(true)?.unwrap();
+    /// Precedence climbing, takes the binops after l with at least min_weight.
+    /// The right operand takes the binops after it that bind tighter than btype,
+    /// or as tight for right associative ones.
+    fn parse_binop(&mut self, mut l: Node, min_weight: i32) -> Result<Node, Error> {
+        while self.curr().ttype.is_binop() && self.curr().ttype.binop_weight() >= min_weight {
+            let line: usize = self.curr().line;
+            let btype: TokenType = self.curr().ttype;
+            self.expect(btype)?;
+
+            if btype == TokenType::Question {
+                l = self.parse_ternary(l, line)?;
+                continue;
+            }
 
-        let n: Node = Node::new(
-            NodeVariant::Binop {
-                btype,
-                l: l.clone(),
-                r,
-            },
-            line,
-        );
-
-        if self.curr.ttype.is_binop() {
-            // curr has higher precedence than btype
-            if btype.binop_weight() < self.curr.ttype.binop_weight() {
-                Ok(Node::new(
-                    NodeVariant::Binop {
-                        btype,
-                        l,
-                        r: self.parse_expr(false)?.unwrap(),
-                    },
-                    self.curr.line,
-                ))
+            let r_weight: i32 = if btype.is_right_assoc() {
+                btype.binop_weight()
             } else {
-                self.prev_expr = n;
-                self.parse_binop()
-            }
-        } else {
-            Ok(n)
+                btype.binop_weight() + 1
+            };
+            // Only parse expression between operators
+            let r: Node = self.parse_expr(true)?.unwrap();
+            let r: Node = self.parse_binop(r, r_weight)?;
+
+            let span: Span = l.span.to(r.span);
+            l = Node::new(NodeVariant::Binop { btype, l, r }, line).with_span(span);
         }
+
+        Ok(l)
+    }
+
+    /// Starts after '?'. The middle operand can be any expression, and the last one
+    /// takes the ?: operators after it, so ?: groups to the right.
+    fn parse_ternary(&mut self, cond: Node, line: usize) -> Result<Node, Error> {
+        let l: Node = self.parse_comma_expr()?.unwrap();
+        self.expect(TokenType::Colon)?;
+        let r: Node = self.parse_expr(true)?.unwrap();
+        let r: Node = self.parse_binop(r, TokenType::Question.binop_weight())?;
+
+        let span: Span = cond.span.to(r.span);
+        Ok(Node::new(NodeVariant::Ternary { cond, l, r }, line).with_span(span))
     }
 
     fn parse_struct(&mut self) -> Result<Node, Error> {
-        if self.lexer.peek(2)?.ttype == TokenType::Lbrace {
+        if self.tokens.peek(2).ttype == TokenType::Lbrace {
             self.parse_struct_def()
         } else {
             self.parse_vardef()
@@ -371,11 +521,12 @@ impl Parser {
     }
 
     fn parse_struct_def(&mut self) -> Result<Node, Error> {
-        let line: usize = self.curr.line;
-        self.expect(TokenType::Id)?; // struct keyword
+        let line: usize = self.curr().line;
+        let start: Span = self.curr().span;
+        self.expect(TokenType::Keyword(Keyword::Struct))?;
 
-        let name: String = self.curr.value.clone();
-        self.expect(TokenType::Id)?; // struct name
+        let name: String = self.expect_id()?;
+        let span: Span = self.span_from(start);
 
         self.expect(TokenType::Lbrace)?;
         let mut fields: Vec<Node> = Vec::new();
@@ -386,20 +537,25 @@ impl Parser {
         self.expect(TokenType::Rbrace)?;
         self.expect(TokenType::Semi)?;
 
-        Ok(Node::new(NodeVariant::Struct { name, fields }, line))
+        Ok(Node::new(NodeVariant::Struct { name, fields }, line).with_span(span))
     }
 
     fn parse_for(&mut self) -> Result<Node, Error> {
-        let line: usize = self.curr.line;
-        self.expect(TokenType::Id)?;
+        let line: usize = self.curr().line;
+        let start: Span = self.curr().span;
+        self.expect(TokenType::Keyword(Keyword::For))?;
 
+        // Each part can be left out
         self.expect(TokenType::Lparen)?;
-        let init: Node = self.parse_expr(false)?.unwrap();
-        let cond: Node = self.parse_expr(false)?.unwrap();
-        let inc: Node = self.parse_expr(false)?.unwrap();
+        let init: Node = self.parse_for_part(TokenType::Semi)?;
+        self.expect(TokenType::Semi)?;
+        let cond: Node = self.parse_for_part(TokenType::Semi)?;
+        self.expect(TokenType::Semi)?;
+        let inc: Node = self.parse_for_part(TokenType::Rparen)?;
         self.expect(TokenType::Rparen)?;
+        let span: Span = self.span_from(start);
 
-        let body: Node = self.parse_expr(false)?.unwrap();
+        let body: Node = self.parse_stmt()?.unwrap();
 
         Ok(Node::new(
             NodeVariant::For {
@@ -409,23 +565,114 @@ impl Parser {
                 body,
             },
             line,
-        ))
+        )
+        .with_span(span))
+    }
+
+    fn parse_for_part(&mut self, end: TokenType) -> Result<Node, Error> {
+        if self.curr().ttype == end {
+            Ok(Node::new(NodeVariant::Noop, self.curr().line))
+        } else {
+            Ok(self.parse_comma_expr()?.unwrap())
+        }
     }
 
     fn parse_while(&mut self) -> Result<Node, Error> {
-        let line: usize = self.curr.line;
-        self.expect(TokenType::Id)?;
+        let line: usize = self.curr().line;
+        let start: Span = self.curr().span;
+        self.expect(TokenType::Keyword(Keyword::While))?;
 
         self.expect(TokenType::Lparen)?;
-        let cond: Node = self.parse_expr(false)?.unwrap();
+        let cond: Node = self.parse_comma_expr()?.unwrap();
         self.expect(TokenType::Rparen)?;
+        let span: Span = self.span_from(start);
+
+        let body: Node = self.parse_stmt()?.unwrap();
+        Ok(Node::new(NodeVariant::While { cond, body }, line).with_span(span))
+    }
+
+    fn parse_do_while(&mut self) -> Result<Node, Error> {
+        let line: usize = self.curr().line;
+        let start: Span = self.curr().span;
+        self.expect(TokenType::Keyword(Keyword::Do))?;
+
+        let body: Node = self.parse_stmt()?.unwrap();
+        // The body statement's ';' comes before while
+        if self.curr().ttype == TokenType::Semi && self.tokens.peek(1).ttype == TokenType::Keyword(Keyword::While) {
+            self.expect(TokenType::Semi)?;
+        }
+
+        self.expect(TokenType::Keyword(Keyword::While))?;
+        self.expect(TokenType::Lparen)?;
+        let cond: Node = self.parse_comma_expr()?.unwrap();
+        self.expect(TokenType::Rparen)?;
+
+        Ok(Node::new(NodeVariant::DoWhile { body, cond }, line).with_span(self.span_from(start)))
+    }
+
+    /// break, continue or goto label
+    fn parse_jump(&mut self, keyword: Keyword) -> Result<Node, Error> {
+        let line: usize = self.curr().line;
+        let start: Span = self.curr().span;
+        self.expect(TokenType::Keyword(keyword))?;
+
+        let variant: NodeVariant = match keyword {
+            Keyword::Break => NodeVariant::Break,
+            Keyword::Continue => NodeVariant::Continue,
+            _ => NodeVariant::Goto { name: self.expect_id()? },
+        };
+        Ok(Node::new(variant, line).with_span(self.span_from(start)))
+    }
+
+    /// name: and the statement it labels, which can be empty at the end of a block
+    fn parse_label(&mut self) -> Result<Node, Error> {
+        let line: usize = self.curr().line;
+        let span: Span = self.curr().span;
+        let name: String = self.expect_id()?;
+        self.expect(TokenType::Colon)?;
+
+        let label: Node = Node::new(NodeVariant::Label { name }, line).with_span(span);
+        let stmt: Node = match self.parse_stmt()? {
+            Some(x) => x,
+            None => Node::new(NodeVariant::Noop, self.curr().line),
+        };
+        Ok(Node::new(NodeVariant::Cpd { values: vec![label, stmt] }, line).with_span(span))
+    }
+
+    fn parse_switch(&mut self) -> Result<Node, Error> {
+        let line: usize = self.curr().line;
+        let start: Span = self.curr().span;
+        self.expect(TokenType::Keyword(Keyword::Switch))?;
+
+        self.expect(TokenType::Lparen)?;
+        let cond: Node = self.parse_comma_expr()?.unwrap();
+        self.expect(TokenType::Rparen)?;
+        let span: Span = self.span_from(start);
+
+        let body: Node = self.parse_stmt()?.unwrap();
+        Ok(Node::new(NodeVariant::Switch { cond, body }, line).with_span(span))
+    }
+
+    /// case value: or default:, the value is checked to be constant in codegen
+    fn parse_case(&mut self, keyword: Keyword) -> Result<Node, Error> {
+        let line: usize = self.curr().line;
+        let start: Span = self.curr().span;
+        self.expect(TokenType::Keyword(keyword))?;
+
+        let value: Node = if keyword == Keyword::Case {
+            self.parse_expr(false)?.unwrap()
+        } else {
+            Node::new(NodeVariant::Noop, line)
+        };
+        let span: Span = self.span_from(start);
+        self.expect(TokenType::Colon)?;
 
-        let body: Node = self.parse_expr(false)?.unwrap();
-        Ok(Node::new(NodeVariant::While { cond, body }, line))
+        Ok(Node::new(NodeVariant::Case { value }, line).with_span(span))
     }
 
     fn parse_init_list(&mut self) -> Result<Node, Error> {
-        let line: usize = self.curr.line;
+        let line: usize = self.curr().line;
+        let start: Span = self.curr().span;
 
         self.expect(TokenType::Lparen)?;
         let dtype: Dtype = self.parse_dtype()?;
@@ -435,13 +682,12 @@ impl Parser {
         let mut fields: Vec<(String, Node)> = Vec::new();
         loop {
             self.expect(TokenType::Dot)?;
-            let id: String = self.curr.value.clone();
-            self.expect(TokenType::Id)?;
+            let id: String = self.expect_id()?;
             self.expect(TokenType::Equal)?;
             let expr: Node = self.parse_expr(false)?.unwrap();
             fields.push((id, expr));
 
-            if self.curr.ttype != TokenType::Rbrace {
+            if self.curr().ttype != TokenType::Rbrace {
                 self.expect(TokenType::Comma)?;
             } else {
                 break;
@@ -449,6 +695,6 @@ impl Parser {
         }
         self.expect(TokenType::Rbrace)?;
 
-        Ok(Node::new(NodeVariant::InitList { dtype, fields }, line))
+        Ok(Node::new(NodeVariant::InitList { dtype, fields }, line).with_span(self.span_from(start)))
     }
 }
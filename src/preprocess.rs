@@ -1,4 +1,7 @@
+use crate::error::{Error, ErrorType};
+use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
 
 #[derive(Clone, PartialEq, Debug)]
 struct Definition {
@@ -14,13 +17,25 @@ enum IfType {
 struct IfPair {
     variant: IfType,
     if_expr: String,
+    /// Where the directive starts in the result
     start: usize,
 }
 
 pub struct Preprocessor {
     prog: String,
+    /// Name of the file being preprocessed, used in line markers
+    file: String,
     defs: Vec<Vec<Definition>>,
     pending_ifs: Vec<IfPair>,
+    /// Line and file of the line being processed, following line markers
+    line: usize,
+    line_file: String,
+    /// -I directories, searched before system directories
+    include_dirs: Vec<String>,
+    /// -isystem directories
+    system_include_dirs: Vec<String>,
+    /// -include files, included before the first line of the program
+    forced_includes: Vec<String>,
 }
 
 impl Definition {
@@ -43,162 +58,461 @@ impl IfPair {
 }
 
 impl Preprocessor {
-    pub fn new(prog: &str) -> Self {
+    pub fn new(prog: &str, file: &str) -> Self {
         Self {
             prog: prog.to_string(),
+            file: file.to_string(),
             defs: vec![Vec::new()],
             pending_ifs: Vec::new(),
+            line: 1,
+            line_file: file.to_string(),
+            include_dirs: Vec::new(),
+            system_include_dirs: Vec::new(),
+            forced_includes: Vec::new(),
         }
     }
 
-    pub fn preprocess(&mut self) {
-        while self.prog.contains('#') {
-            self.preprocess_once();
+    /// Predefine a macro, like -D
+    pub fn define(&mut self, name: &str, expr: Option<&str>) {
+        self.undefine(name);
+        self.defs[0].push(Definition::new(name, expr));
+    }
+
+    /// Remove a predefined macro, like -U
+    pub fn undefine(&mut self, name: &str) {
+        self.defs[0].retain(|x| x.name != name);
+    }
+
+    pub fn add_include_dir(&mut self, dir: &str) {
+        self.include_dirs.push(dir.to_string());
+    }
+
+    pub fn add_system_include_dir(&mut self, dir: &str) {
+        self.system_include_dirs.push(dir.to_string());
+    }
+
+    /// Include a file before the first line of the program, like -include
+    pub fn force_include(&mut self, path: &str) {
+        self.forced_includes.push(path.to_string());
+    }
+
+    pub fn preprocess(&mut self) -> Result<(), Error> {
+        // Comments go first, so they can't hide or contain directives
+        self.prog = strip_comments(&self.prog);
+        // Directives end at a newline, so the last line gets one if it's missing
+        if !self.prog.ends_with('\n') {
+            self.prog.push('\n');
         }
 
+        // Line markers in the result map every line back to the file it came from
+        self.prog.insert_str(0, line_marker(1, &self.file, None).as_str());
+
+        // Forced includes are resolved relative to the working directory
+        if !self.forced_includes.is_empty() {
+            let mut includes: String = line_marker(1, "<command-line>", None);
+            for path in &self.forced_includes {
+                includes.push_str(format!("#include \"{}\"\n", path).as_str());
+            }
+            self.prog.insert_str(0, includes.as_str());
+        }
+
+        // Directives run in one pass, which copies the other lines into the result
+        let prog: String = std::mem::take(&mut self.prog);
+        let mut res: String = String::with_capacity(prog.len());
+        self.process_text(&prog, &mut res)?;
+        self.prog = res;
+
         self.replace_defs();
+        Ok(())
     }
 
-    fn preprocess_once(&mut self) {
-        for mut i in 0..self.prog.len() {
-            let start: usize = i;
-            if self.prog.chars().nth(i).unwrap() == '#' {
-                i += 1;
-                let mut cmd: String = String::new();
-                while !self.prog.chars().nth(i).unwrap().is_whitespace() {
-                    cmd += self.prog.chars().nth(i).unwrap().to_string().as_str();
-                    i += 1;
-                }
+    /// Byte at index as a char, directives and macro names are ASCII
+    fn at(&self, index: usize) -> char {
+        self.prog.as_bytes()[index] as char
+    }
+
+    /// Run the directives of text in order, copying the other lines into res.
+    /// Included files are processed where they're included.
+    fn process_text(&mut self, text: &str, res: &mut String) -> Result<(), Error> {
+        for line in text.split_inclusive('\n') {
+            // Directives have to start a line
+            let rest: &str = line.trim_start();
+            let Some(directive) = rest.strip_prefix('#') else {
+                res.push_str(line);
+                self.line += 1;
+                continue;
+            };
+
+            // Line markers are copied, the line after one is the line it names
+            if let Some((number, file)) = parse_line_marker(directive) {
+                res.push_str(line);
+                self.line = number;
+                self.line_file = file.to_string();
+                continue;
+            }
 
-                match cmd.as_str() {
-                    "include" => return self.process_include(start, i),
-                    "define" => return self.process_define(start, i),
-                    "ifndef" => return self.process_if(start, i, IfType::Ifndef),
-                    "ifdef" => return self.process_if(start, i, IfType::Ifdef),
-                    "endif" => return self.process_endif(start, i),
-                    _ => panic!(),
+            // Indentation is kept, the directive itself is removed
+            res.push_str(&line[..line.len() - rest.len()]);
+            let (cmd, args) = split_word(directive);
+            match cmd {
+                "include" => self.process_include(args, res)?,
+                "define" => self.process_define(args, res),
+                "ifndef" => self.process_if(args, res, IfType::Ifndef),
+                "ifdef" => self.process_if(args, res, IfType::Ifdef),
+                "endif" => self.process_endif(args, res)?,
+                _ => {
+                    return Err(Error::new(ErrorType::UnknownDirective(cmd), self.line)
+                        .in_file(self.line_file.as_str()));
                 }
             }
         }
+
+        Ok(())
     }
 
     fn replace_defs(&mut self) {
-        for layer in self.defs.clone() {
-            for def in &layer {
-                self.prog = self.prog.replace(
-                    def.name.as_str(),
-                    if let Some(id) = def.expr.clone() {
-                        id
-                    } else {
-                        String::new()
+        // Later definitions replace earlier ones
+        let defs: HashMap<&str, &Definition> = self.defs.iter().flatten().map(|x| (x.name.as_str(), x)).collect();
+        let mut res: String = String::with_capacity(self.prog.len());
+        let mut i: usize = 0;
+        let mut line_start: bool = true;
+
+        while i < self.prog.len() {
+            let ch: char = self.at(i);
+            let start: usize = i;
+
+            if ch == '#' && line_start {
+                // Line markers are copied as is
+                while i < self.prog.len() && self.at(i) != '\n' {
+                    i += 1;
+                }
+            } else if ch == '"' || ch == '\'' {
+                // String and char literals aren't expanded
+                i += 1;
+                while i < self.prog.len() && self.at(i) != ch && self.at(i) != '\n' {
+                    if self.at(i) == '\\' {
+                        i += 1;
                     }
-                    .as_str(),
-                );
+                    i += 1;
+                }
+                i = (i + 1).min(self.prog.len());
+            } else if ch.is_ascii_alphabetic() || ch == '_' {
+                while i < self.prog.len() && (self.at(i).is_ascii_alphanumeric() || self.at(i) == '_') {
+                    i += 1;
+                }
+
+                res.push_str(self.expand(&self.prog[start..i], &defs, &mut Vec::new()).as_str());
+                line_start = false;
+                continue;
+            } else {
+                // Copy whole utf-8 characters
+                i += self.prog[i..].chars().next().unwrap().len_utf8();
+            }
+
+            res.push_str(&self.prog[start..i]);
+            if ch == '\n' {
+                line_start = true;
+            } else if !ch.is_whitespace() {
+                line_start = false;
             }
         }
+
+        self.prog = res;
     }
 
-    fn process_include(&mut self, start: usize, mut index: usize) {
-        while self.prog.chars().nth(index).unwrap() != '"' {
-            index += 1;
+    /// Fully expanded replacement of an identifier.
+    /// Macros currently being expanded aren't expanded again, like in C.
+    fn expand(&self, id: &str, defs: &HashMap<&str, &Definition>, expanding: &mut Vec<String>) -> String {
+        if expanding.iter().any(|x| x == id) {
+            return id.to_string();
         }
-        index += 1;
 
-        let mut path: String = String::new();
-        while self.prog.chars().nth(index).unwrap() != '"' {
-            path.push(self.prog.chars().nth(index).unwrap());
-            index += 1;
+        let Some(def) = defs.get(id) else {
+            return id.to_string();
+        };
+
+        expanding.push(id.to_string());
+        let expr: String = def.expr.clone().unwrap_or_default();
+        let mut res: String = String::new();
+        let mut word: String = String::new();
+        for ch in expr.chars().chain(std::iter::once(' ')) {
+            if ch.is_ascii_alphanumeric() || ch == '_' {
+                word.push(ch);
+                continue;
+            }
+
+            if !word.is_empty() {
+                res.push_str(self.expand(&word, defs, expanding).as_str());
+                word.clear();
+            }
+            res.push(ch);
         }
-        index += 1;
+        expanding.pop();
+
+        // Remove the padding char
+        res.pop();
+        res
+    }
+
+    /// args is the rest of the line after "#include"
+    fn process_include(&mut self, args: &str, res: &mut String) -> Result<(), Error> {
+        let (line, file): (usize, String) = (self.line, self.line_file.clone());
+        let not_found = |name: &str| Error::new(ErrorType::IncludeNotFound(name), line).in_file(file.as_str());
+
+        let Some(open) = args.find(['"', '<']) else {
+            return Err(not_found(args.trim()));
+        };
+        let close: char = if args[open..].starts_with('<') { '>' } else { '"' };
+        let Some(len) = args[open + 1..].find(close) else {
+            return Err(not_found(args[open + 1..].trim_end()));
+        };
+        let name: &str = &args[open + 1..open + 1 + len];
 
-        self.prog.replace_range(
-            start..index,
-            fs::read_to_string(path.as_str()).unwrap().as_str(),
-        );
+        let path: String = self
+            .resolve_include(name, &file, close == '"')
+            .ok_or_else(|| not_found(name))?;
+        let contents: String = fs::read_to_string(path.as_str()).map_err(|e| {
+            Error::new(ErrorType::ReadFile(path.as_str(), e.to_string()), line)
+                .in_file(file.as_str())
+        })?;
+
+        // Mark where the included file starts, and where the current one resumes.
+        // The resuming marker refers to the line after the include.
+        res.push_str(line_marker(1, &path, Some(1)).as_str());
+        self.line = 1;
+        self.line_file = path.clone();
+        self.process_text(&strip_comments(&contents), res)?;
+
+        res.push('\n');
+        res.push_str(line_marker(line + 1, &file, Some(2)).trim_end());
+        res.push_str(&args[open + 1 + len + 1..]);
+        self.line = line + 1;
+        self.line_file = file;
+        Ok(())
     }
 
-    fn process_define(&mut self, start: usize, mut index: usize) {
-        while self.prog.chars().nth(index).unwrap().is_whitespace() {
-            index += 1;
+    /// Search order is the including file's directory for "quoted" includes,
+    /// then -I directories, then -isystem directories.
+    fn resolve_include(&self, name: &str, includer: &str, quoted: bool) -> Option<String> {
+        let includer_dir: &str = Path::new(includer)
+            .parent()
+            .and_then(|x| x.to_str())
+            .unwrap_or("");
+
+        let mut dirs: Vec<&str> = Vec::new();
+        if quoted {
+            dirs.push(includer_dir);
         }
+        dirs.extend(self.include_dirs.iter().map(String::as_str));
+        dirs.extend(self.system_include_dirs.iter().map(String::as_str));
+
+        dirs.iter()
+            .map(|dir| Path::new(dir).join(name))
+            .find(|path| path.is_file())
+            .map(|path| path.to_string_lossy().to_string())
+    }
+
+    /// args is the rest of the line after "#define", the definition ends with it
+    fn process_define(&mut self, args: &str, res: &mut String) {
+        let (id, expr) = split_word(args);
+        let expr: &str = expr.trim();
 
-        let mut id: String = String::new();
-        while !self.prog.chars().nth(index).unwrap().is_whitespace() {
-            id.push(self.prog.chars().nth(index).unwrap());
-            index += 1;
+        // self.defs is guaranteed to have last element, a redefinition replaces the old one
+        let layer: &mut Vec<Definition> = self.defs.iter_mut().last().unwrap();
+        layer.retain(|x| x.name != id);
+        layer.push(Definition::new(id, if expr.is_empty() { None } else { Some(expr) }));
+
+        if args.ends_with('\n') {
+            res.push('\n');
         }
+        self.line += 1;
+    }
+
+    fn process_if(&mut self, args: &str, res: &mut String, variant: IfType) {
+        self.defs.push(Vec::new());
+        let (id, rest) = split_word(args);
+        self.pending_ifs.push(IfPair::new(variant, id, res.len()));
+        res.push_str(rest);
+        self.line += 1;
+    }
+
+    /// Whether the contents since the matching #ifdef or #ifndef stay is only decided here,
+    /// so the directives in them have already run.
+    fn process_endif(&mut self, args: &str, res: &mut String) -> Result<(), Error> {
+        let Some(last) = self.pending_ifs.pop() else {
+            return Err(Error::new(ErrorType::EndifWithoutIf, self.line).in_file(self.line_file.as_str()));
+        };
 
-        let mut expr: String = String::new();
-        if self.prog.chars().nth(index).unwrap() != '\n' {
-            while self.prog.chars().nth(index).unwrap().is_whitespace() {
-                index += 1;
+        // For ifndef
+        let mut exists: bool = false;
+        for l in 0..self.defs.len() - 1 {
+            for def in &self.defs[l] {
+                if def.name == last.if_expr {
+                    exists = true;
+                    break;
+                }
             }
+        }
 
-            while self.prog.chars().nth(index).unwrap() != '\n' {
-                expr.push(self.prog.chars().nth(index).unwrap());
-                index += 1;
+        let removed: bool = match last.variant {
+            IfType::Ifndef => exists,
+            IfType::Ifdef => !exists,
+        };
+        if removed {
+            // Remove contents, and mark which line comes after the endif since
+            // the removed lines may have included other files.
+            res.truncate(last.start);
+            if !res.is_empty() && !res.ends_with('\n') {
+                res.push('\n');
             }
+            res.push_str(line_marker(self.line + 1, &self.line_file, None).trim_end());
         }
+        res.push_str(args);
+        self.line += 1;
 
-        // self.defs is guaranteed to have last element
-        self.defs.iter_mut().last().unwrap().push(Definition::new(
-            id.as_str(),
-            if expr.is_empty() { None } else { Some(&expr) },
-        ));
-        self.prog.replace_range(start..index, "");
+        // Move all defs in current scope up one layer, so they're globally accessible.
+        // Defs in removed contents are dropped.
+        let last: Vec<Definition> = self.defs.pop().unwrap();
+        if !removed {
+            let parent: &mut Vec<Definition> = self.defs.iter_mut().last().unwrap();
+            parent.retain(|x| !last.iter().any(|def| def.name == x.name));
+            parent.extend(last);
+        }
+        Ok(())
     }
 
-    fn process_if(&mut self, start: usize, mut index: usize, variant: IfType) {
-        self.defs.push(Vec::new());
-        while self.prog.chars().nth(index).unwrap().is_whitespace() {
-            index += 1;
+    pub fn result(&self) -> String {
+        self.prog.clone()
+    }
+}
+
+/// Source of file with the lines macros changed taken from processed, so spans in them
+/// line up. The rest come from original, keeping their comments and directives.
+pub fn expanded_source(processed: &str, file: &str, original: &str) -> String {
+    let stripped: String = strip_comments(original);
+    let stripped_lines: Vec<&str> = stripped.split('\n').collect();
+    let mut lines: Vec<&str> = original.split('\n').collect();
+
+    let mut in_file: bool = false;
+    let mut line: usize = 1;
+    for text in processed.split('\n') {
+        if let Some((number, marker_file)) = text.strip_prefix('#').and_then(parse_line_marker) {
+            line = number;
+            in_file = marker_file == file;
+            continue;
         }
 
-        let mut id: String = String::new();
-        while !self.prog.chars().nth(index).unwrap().is_whitespace() {
-            id.push(self.prog.chars().nth(index).unwrap());
-            index += 1;
+        if in_file && line <= lines.len() && !text.trim().is_empty() && stripped_lines.get(line - 1) != Some(&text) {
+            lines[line - 1] = text;
         }
+        line += 1;
+    }
 
-        self.pending_ifs
-            .push(IfPair::new(variant, id.as_str(), start));
-        self.prog.replace_range(start..index, "");
+    lines.join("\n")
+}
+
+/// GNU-style "# <line> "<file>" <flag>" marker: the line after it is line `line` of `file`.
+/// Flag 1 marks entering an included file, 2 marks returning from one.
+fn line_marker(line: usize, file: &str, flag: Option<u8>) -> String {
+    match flag {
+        Some(flag) => format!("# {} \"{}\" {}\n", line, file, flag),
+        None => format!("# {} \"{}\"\n", line, file),
+    }
+}
+
+/// Line and file named by a line marker, text is what follows its '#'
+fn parse_line_marker(text: &str) -> Option<(usize, &str)> {
+    let text: &str = text.trim_start_matches([' ', '\t']);
+    if !text.starts_with(|x: char| x.is_ascii_digit()) {
+        return None;
     }
 
-    fn process_endif(&mut self, start: usize, index: usize) {
-        self.prog.replace_range(start..index, "");
-        if let Some(last) = self.pending_ifs.last() {
-            // For ifndef
-            let mut exists: bool = false;
-            for l in 0..self.defs.len() - 1 {
-                for def in &self.defs[l] {
-                    if def.name == last.if_expr {
-                        exists = true;
-                        break;
+    let mut parts = text.split('"');
+    let line: usize = parts.next().unwrap_or("").trim().parse::<usize>().unwrap_or(1);
+    Some((line, parts.next().unwrap_or("")))
+}
+
+/// First word of text after leading blanks, and the rest of text after it.
+/// A newline ends the word, but isn't skipped before it.
+fn split_word(text: &str) -> (&str, &str) {
+    let text: &str = text.trim_start_matches(|x: char| x.is_whitespace() && x != '\n');
+    text.split_at(text.find(char::is_whitespace).unwrap_or(text.len()))
+}
+
+/// Comments blanked out with spaces, ignoring comment markers inside literals.
+/// Newlines inside block comments stay in place, so every line and column keeps its
+/// position. In directives they're moved to the end of the line the comment ends on,
+/// so the directive stays on one line and the lines after keep their numbers.
+/// Unterminated block comments are kept for the lexer to report.
+fn strip_comments(prog: &str) -> String {
+    let bytes: &[u8] = prog.as_bytes();
+    let mut res: String = String::with_capacity(prog.len());
+    let mut newlines: usize = 0;
+    let mut directive: bool = starts_directive(prog);
+    let mut copied: usize = 0;
+    let mut i: usize = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote && bytes[i] != b'\n' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
                     }
+                    i += 1;
                 }
+                i = (i + 1).min(bytes.len());
             }
-
-            if match last.variant {
-                IfType::Ifndef => exists,
-                IfType::Ifdef => !exists,
-            } {
-                // Remove contents
-                self.prog.replace_range(last.start..start, "");
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                let end: usize = prog[i..].find('\n').map_or(bytes.len(), |x| i + x);
+                res.push_str(&prog[copied..i]);
+                newlines += blank_comment(&mut res, &prog[i..end], directive);
+                i = end;
+                copied = i;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let Some(len) = prog[i + 2..].find("*/") else {
+                    break;
+                };
+                let end: usize = i + 2 + len + 2;
+                res.push_str(&prog[copied..i]);
+                newlines += blank_comment(&mut res, &prog[i..end], directive);
+                i = end;
+                copied = i;
+            }
+            b'\n' => {
+                i += 1;
+                res.push_str(&prog[copied..i]);
+                res.push_str("\n".repeat(newlines).as_str());
+                newlines = 0;
+                directive = starts_directive(&prog[i..]);
+                copied = i;
             }
+            _ => i += 1,
+        }
+    }
 
-            self.pending_ifs.pop();
+    res.push_str(&prog[copied..]);
+    res.push_str("\n".repeat(newlines).as_str());
+    res
+}
 
-            // Move all defs in current scope up one layer, so they're globally accessible
-            let last: Vec<Definition> = self.defs.pop().unwrap();
-            self.defs.iter_mut().last().unwrap().extend(last);
-        } else {
-            eprintln!("preprocessing error: endif without if");
-            std::process::exit(1);
+/// Spaces for each char of comment, keeping newlines outside of directives.
+/// Returns the number of newlines left out.
+fn blank_comment(res: &mut String, comment: &str, directive: bool) -> usize {
+    let mut newlines: usize = 0;
+    for ch in comment.chars() {
+        match ch {
+            '\n' if directive => newlines += 1,
+            '\n' => res.push('\n'),
+            _ => res.push(' '),
         }
     }
+    newlines
+}
 
-    pub fn result(&self) -> String {
-        self.prog.clone()
-    }
+/// The line at the start of text is a directive
+fn starts_directive(text: &str) -> bool {
+    text.trim_start_matches([' ', '\t']).starts_with('#')
 }
@@ -0,0 +1,114 @@
+use crate::error::{Error, ErrorType};
+use std::fmt;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, Command, ExitStatus};
+use std::time::{Duration, Instant};
+
+/// How a program run by `cc run` ended
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum RunStatus {
+    Exited(i32),
+    /// Signal number
+    Signaled(i32),
+    /// Killed after running longer than the timeout
+    TimedOut(Duration),
+}
+
+impl RunStatus {
+    /// Exit code for the driver, following shell conventions for signals and timeouts
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RunStatus::Exited(code) => *code,
+            RunStatus::Signaled(sig) => 128 + sig,
+            RunStatus::TimedOut(_) => 124,
+        }
+    }
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunStatus::Exited(code) => write!(f, "exited with status {}", code),
+            RunStatus::Signaled(sig) => match signal_name(*sig) {
+                Some(name) => write!(f, "terminated by signal {} ({})", name, sig),
+                None => write!(f, "terminated by signal {}", sig),
+            },
+            RunStatus::TimedOut(timeout) => {
+                write!(f, "timed out after {}s", timeout.as_secs_f64())
+            }
+        }
+    }
+}
+
+/// Name of a Linux signal number
+pub fn signal_name(sig: i32) -> Option<&'static str> {
+    Some(match sig {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        5 => "SIGTRAP",
+        6 => "SIGABRT",
+        7 => "SIGBUS",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        10 => "SIGUSR1",
+        11 => "SIGSEGV",
+        12 => "SIGUSR2",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        16 => "SIGSTKFLT",
+        17 => "SIGCHLD",
+        18 => "SIGCONT",
+        19 => "SIGSTOP",
+        20 => "SIGTSTP",
+        21 => "SIGTTIN",
+        22 => "SIGTTOU",
+        23 => "SIGURG",
+        24 => "SIGXCPU",
+        25 => "SIGXFSZ",
+        26 => "SIGVTALRM",
+        27 => "SIGPROF",
+        28 => "SIGWINCH",
+        29 => "SIGIO",
+        30 => "SIGPWR",
+        31 => "SIGSYS",
+        _ => return None,
+    })
+}
+
+/// Run an executable with inherited stdin, stdout and stderr
+pub fn run(exe_path: &str, args: &[String], timeout: Option<Duration>) -> Result<RunStatus, Error> {
+    let mut child: Child = Command::new(exe_path)
+        .args(args)
+        .spawn()
+        .map_err(|e| Error::new(ErrorType::ToolSpawn(exe_path, e.to_string()), 0))?;
+
+    let wait_err = |e: std::io::Error| Error::new(ErrorType::ToolSpawn(exe_path, e.to_string()), 0);
+    let status: ExitStatus = match timeout {
+        None => child.wait().map_err(wait_err)?,
+        Some(timeout) => {
+            let start: Instant = Instant::now();
+            loop {
+                if let Some(status) = child.try_wait().map_err(wait_err)? {
+                    break status;
+                }
+
+                if start.elapsed() >= timeout {
+                    child.kill().ok();
+                    child.wait().ok();
+                    return Ok(RunStatus::TimedOut(timeout));
+                }
+
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        }
+    };
+
+    Ok(match (status.code(), status.signal()) {
+        (Some(code), _) => RunStatus::Exited(code),
+        (None, Some(sig)) => RunStatus::Signaled(sig),
+        (None, None) => RunStatus::Exited(1),
+    })
+}
@@ -1,6 +1,7 @@
 use crate::cdefs::{CFdef, CStruct, CVardef};
 use crate::error::{Error, ErrorType};
 use crate::node::{Dtype, DtypeVariant, Node, NodeVariant};
+use std::collections::HashMap;
 
 pub struct ScopeLayer {
     vardefs: Vec<CVardef>,
@@ -27,14 +28,23 @@ impl ScopeLayer {
 pub struct Scope {
     layers: Vec<ScopeLayer>,
     fdefs: Vec<CFdef>,
+    /// Index in fdefs of each function, so lookups don't slow down as the unit grows
+    fdef_indices: HashMap<String, usize>,
     structs: Vec<CStruct>,
 }
 
+impl Default for Scope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Scope {
     pub fn new() -> Self {
         Self {
             layers: vec![ScopeLayer::new()],
             fdefs: Vec::new(),
+            fdef_indices: HashMap::new(),
             structs: Vec::new(),
         }
     }
@@ -82,20 +92,25 @@ impl Scope {
                     return Err(Error::new(
                         ErrorType::FunctionDeclDefMismatch(fname.as_str()),
                         n.line,
-                    ));
+                    )
+                    .with_note("Declared here.", fdef.node.span));
                 }
 
-                // Keep all fdefs with name != fname
-                self.fdefs.retain(|x| {
-                    let NodeVariant::Fdef { name, .. } = x.node.variant.as_ref() else { unreachable!() };
-                    name != fname
-                });
             } else {
-                return Err(Error::new(ErrorType::DuplicateFdef(fname.as_str()), n.line));
+                return Err(Error::new(ErrorType::DuplicateFdef(fname.as_str()), n.line)
+                    .with_note("Previously defined here.", fdef.node.span));
             }
         }
 
-        self.fdefs.push(CFdef::new(n, self)?);
+        let fdef: CFdef = CFdef::new(n, self)?;
+        match self.fdef_indices.get(fname) {
+            // The declaration is replaced where it was
+            Some(&i) => self.fdefs[i] = fdef,
+            None => {
+                self.fdef_indices.insert(fname.clone(), self.fdefs.len());
+                self.fdefs.push(fdef);
+            }
+        }
         Ok(())
     }
 
@@ -109,7 +124,7 @@ impl Scope {
             // If declaration, replace. Otherwise it's a redef error
             if orig_fields.is_empty() {
                 // Remove original struct
-                self.fdefs.retain(|x| {
+                self.structs.retain(|x| {
                     let NodeVariant::Struct { name: sname, .. } = x.node.variant.as_ref() else { unreachable!() };
                     sname != name
                 });
@@ -117,7 +132,8 @@ impl Scope {
                 return Err(Error::new(
                     ErrorType::DuplicateSdef(orig_name.as_str()),
                     n.line,
-                ));
+                )
+                .with_note("Previously defined here.", st.node.span));
             }
         }
 
@@ -125,14 +141,19 @@ impl Scope {
         Ok(())
     }
 
+    pub fn fdefs(&self) -> &[CFdef] {
+        &self.fdefs
+    }
+
+    pub fn structs(&self) -> &[CStruct] {
+        &self.structs
+    }
+
     pub fn find_fdef(&self, name: &str, err_line: usize) -> Result<&CFdef, Error> {
-        self.fdefs.iter().find(|&x| {
-            let NodeVariant::Fdef { name: fname, .. } = x.node.variant.as_ref() else { unreachable!() };
-            fname == name
-        }).ok_or(Error::new(
-            ErrorType::NonexistentFunction(name),
-            err_line
-        ))
+        self.fdef_indices
+            .get(name)
+            .map(|&i| &self.fdefs[i])
+            .ok_or_else(|| Error::new(ErrorType::NonexistentFunction(name), err_line))
     }
 
     pub fn find_struct(&self, name: &str, err_line: usize) -> Result<&CStruct, Error> {
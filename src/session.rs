@@ -0,0 +1,96 @@
+use crate::asm::Gen;
+use crate::driver::{MacroArg, Options};
+use crate::error::{Error, ErrorType};
+use crate::lexer::{Lexer, Token};
+use crate::node::Node;
+use crate::parser::Parser;
+use crate::preprocess::Preprocessor;
+use std::process::{Command, Output};
+
+/// Compiler configured by a set of options, with one method per stage.
+/// Every stage is independent, so tools can start or stop at any of them.
+pub struct Session {
+    options: Options,
+}
+
+impl Session {
+    pub fn new(options: Options) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Expand directives and macros.
+    /// file is used in line markers and to resolve quoted includes.
+    pub fn preprocess(&self, prog: &str, file: &str) -> Result<String, Error> {
+        let mut preprocessor: Preprocessor = Preprocessor::new(prog, file);
+        for arg in &self.options.macros {
+            match arg {
+                MacroArg::Define(name, expr) => preprocessor.define(name, Some(expr)),
+                MacroArg::Undefine(name) => preprocessor.undefine(name),
+            }
+        }
+        for dir in &self.options.include_dirs {
+            preprocessor.add_include_dir(dir);
+        }
+        for dir in &self.options.system_include_dirs {
+            preprocessor.add_system_include_dir(dir);
+        }
+        for path in &self.options.forced_includes {
+            preprocessor.force_include(path);
+        }
+
+        preprocessor.preprocess()?;
+        Ok(preprocessor.result())
+    }
+
+    /// Tokens of preprocessed source, ending with Eof
+    pub fn lex(&self, processed: &str) -> Result<Vec<Token>, Error> {
+        Lexer::tokenize(processed)
+    }
+
+    pub fn parse(&self, processed: &str) -> Result<Node, Error> {
+        Parser::new(processed)?.parse()
+    }
+
+    /// Generator for one translation unit, its scope is available after generating
+    pub fn generator(&self) -> Gen {
+        Gen::new(!self.options.nostartfiles)
+    }
+
+    /// Type check and generate nasm assembly
+    pub fn generate(&self, root: &Node) -> Result<String, Error> {
+        self.generator().gen(root)
+    }
+
+    pub fn assemble(&self, asm_path: &str, obj_path: &str) -> Result<(), Error> {
+        run_tool("nasm", &["-felf64", asm_path, "-o", obj_path])
+    }
+
+    pub fn link(&self, objs: &[String], exe_path: &str) -> Result<(), Error> {
+        let mut args: Vec<&str> = objs.iter().map(|x| x.as_str()).collect();
+        args.extend(["-o", exe_path]);
+        run_tool("ld", &args)
+    }
+}
+
+/// Run an external tool, keeping its output if it fails
+fn run_tool(tool: &str, args: &[&str]) -> Result<(), Error> {
+    let output: Output = Command::new(tool)
+        .args(args)
+        .output()
+        .map_err(|e| Error::new(ErrorType::ToolSpawn(tool, e.to_string()), 0))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let mut log: String = String::from_utf8_lossy(&output.stdout).to_string();
+    log.push_str(&String::from_utf8_lossy(&output.stderr));
+    Err(Error::new(
+        ErrorType::ToolFailed(tool, output.status.code(), log),
+        0,
+    ))
+}
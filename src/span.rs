@@ -0,0 +1,49 @@
+use std::sync::Mutex;
+
+/// Region of preprocessed source.
+/// Byte offsets and columns are end exclusive, columns are 1-based.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct Span {
+    /// File of the closest line marker before the span, empty without one
+    pub file: &'static str,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    /// From the start of self to the end of other
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            file: self.file,
+            start: self.start,
+            end: other.end,
+            line: self.line,
+            column: self.column,
+            end_line: other.end_line,
+            end_column: other.end_column,
+        }
+    }
+
+    /// Nodes made up by the compiler rather than parsed have no span
+    pub fn is_empty(&self) -> bool {
+        self.line == 0
+    }
+}
+
+/// File names are kept for the rest of the program, so spans can stay Copy.
+/// Each distinct name is only stored once.
+pub fn intern_file(file: &str) -> &'static str {
+    static FILES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+    let mut files = FILES.lock().unwrap();
+    if let Some(interned) = files.iter().find(|x| **x == file) {
+        return interned;
+    }
+
+    let interned: &'static str = Box::leak(file.to_string().into_boxed_str());
+    files.push(interned);
+    interned
+}
@@ -1,4 +1,25 @@
 #!/bin/sh
-cargo r examples/test.c
-./a.out
-echo $?
+# Runs every example and fails if one exits with an unexpected status.
+# Self-checking examples exit with their number of failed checks.
+cargo build -q || exit 1
+
+failed=0
+for file in examples/*.c; do
+    case "$file" in
+        examples/fib.c) expected=34 ;;
+        examples/test.c) expected=5 ;;
+        *) expected=0 ;;
+    esac
+
+    target/debug/cc run "$file"
+    status=$?
+    if [ "$status" -ne "$expected" ]; then
+        echo "$file: expected status $expected, got $status"
+        failed=$((failed + 1))
+    fi
+done
+
+if [ "$failed" -ne 0 ]; then
+    echo "$failed example(s) failed"
+    exit 1
+fi
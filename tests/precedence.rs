@@ -0,0 +1,81 @@
+//! Each expression has to parse to the same tree as its fully parenthesized form.
+
+use cc::driver::Options;
+use cc::dump::{self, DumpFormat};
+use cc::Session;
+
+const PAIRS: &[(&str, &str)] = &[
+    // Left associative levels
+    ("a - b - c", "(a - b) - c"),
+    ("a / b % c * d", "((a / b) % c) * d"),
+    ("a << b >> c", "(a << b) >> c"),
+    ("a < b <= c > d >= e", "(((a < b) <= c) > d) >= e"),
+    ("a == b != c", "(a == b) != c"),
+    ("a && b && c", "(a && b) && c"),
+    ("a || b || c", "(a || b) || c"),
+    ("a, b, c", "(a, b), c"),
+    // Each level binds tighter than the next
+    ("a * b + c", "(a * b) + c"),
+    ("a + b * c", "a + (b * c)"),
+    ("a + b << c", "(a + b) << c"),
+    ("a << b < c", "(a << b) < c"),
+    ("a < b == c", "(a < b) == c"),
+    ("a == b & c", "(a == b) & c"),
+    ("a & b ^ c", "(a & b) ^ c"),
+    ("a ^ b | c", "(a ^ b) | c"),
+    ("a | b && c", "(a | b) && c"),
+    ("a && b || c", "(a && b) || c"),
+    ("a || b && c", "a || (b && c)"),
+    ("a | b ^ c & d", "a | (b ^ (c & d))"),
+    ("a - b * c - d", "(a - (b * c)) - d"),
+    // Right associative levels
+    ("a = b = c", "a = (b = c)"),
+    ("a += b -= c", "a += (b -= c)"),
+    ("a ? b : c ? d : e", "a ? b : (c ? d : e)"),
+    ("a ? b ? c : d : e", "a ? (b ? c : d) : e"),
+    // ?: is below || and above assignment and comma
+    ("a || b ? c : d", "(a || b) ? c : d"),
+    ("a = b ? c : d", "a = (b ? c : d)"),
+    ("a ? b : c, d", "(a ? b : c), d"),
+    ("a = b, c = d", "(a = b), (c = d)"),
+    ("a = b || c", "a = (b || c)"),
+    ("a <<= b + c", "a <<= (b + c)"),
+    // Unary and postfix operators bind tighter than any binop
+    ("-a * b", "(-a) * b"),
+    ("!a && b", "(!a) && b"),
+    ("~a & b", "(~a) & b"),
+    ("*p + 1", "(*p) + 1"),
+    ("a++ + b", "(a++) + b"),
+    ("-a++", "-(a++)"),
+    ("s.x * 2", "(s.x) * 2"),
+];
+
+fn dump(session: &Session, expr: &str) -> String {
+    let prog: String = format!("int main()\n{{\n    {};\n}}\n", expr);
+    let root = session
+        .parse(&prog)
+        .unwrap_or_else(|e| panic!("'{}' doesn't parse: {}", expr, e));
+    dump::dump_ast(&root, DumpFormat::Tree).unwrap()
+}
+
+#[test]
+fn parenthesized() {
+    let session: Session = Session::new(Options::default());
+    for (expr, parenthesized) in PAIRS {
+        assert_eq!(
+            dump(&session, expr),
+            dump(&session, parenthesized),
+            "'{}' should parse as '{}'",
+            expr,
+            parenthesized
+        );
+    }
+}
+
+#[test]
+fn grouping_matters() {
+    // Without this, a parser ignoring parentheses would pass
+    let session: Session = Session::new(Options::default());
+    assert_ne!(dump(&session, "a - b - c"), dump(&session, "a - (b - c)"));
+    assert_ne!(dump(&session, "a * b + c"), dump(&session, "a * (b + c)"));
+}